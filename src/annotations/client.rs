@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::{annotations::Annotation, error::Result, http};
+use crate::{annotations::Annotation, error::Result, http, RequestOptions};
 use tracing::instrument;
 
 use super::requests;
@@ -22,8 +22,21 @@ impl<'client> Client<'client> {
     /// If the API call fails
     #[instrument(skip(self))]
     pub async fn create(&self, req: requests::Create) -> Result<Annotation> {
+        self.create_opt(req, RequestOptions::default()).await
+    }
+
+    /// Creates an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    #[instrument(skip(self, request_options))]
+    pub async fn create_opt(
+        &self,
+        req: requests::Create,
+        request_options: RequestOptions,
+    ) -> Result<Annotation> {
         self.http_client
-            .post("/v2/annotations", req)
+            .post("/v2/annotations", req, request_options)
             .await?
             .json()
             .await
@@ -35,8 +48,21 @@ impl<'client> Client<'client> {
     /// If the API call fails
     #[instrument(skip(self))]
     pub async fn get(&self, id: impl fmt::Display + fmt::Debug) -> Result<Annotation> {
+        self.get_opt(id, RequestOptions::default()).await
+    }
+
+    /// Gets an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    #[instrument(skip(self, request_options))]
+    pub async fn get_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        request_options: RequestOptions,
+    ) -> Result<Annotation> {
         self.http_client
-            .get(format!("/v2/annotations/{id}"))
+            .get(format!("/v2/annotations/{id}"), request_options)
             .await?
             .json()
             .await
@@ -48,9 +74,22 @@ impl<'client> Client<'client> {
     /// If the API call fails
     #[instrument(skip(self))]
     pub async fn list(&self, req: requests::List) -> Result<Vec<Annotation>> {
+        self.list_opt(req, RequestOptions::default()).await
+    }
+
+    /// Lists annotations with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    #[instrument(skip(self, request_options))]
+    pub async fn list_opt(
+        &self,
+        req: requests::List,
+        request_options: RequestOptions,
+    ) -> Result<Vec<Annotation>> {
         let query_params = serde_qs::to_string(&req)?;
         self.http_client
-            .get(format!("/v2/annotations?{query_params}"))
+            .get(format!("/v2/annotations?{query_params}"), request_options)
             .await?
             .json()
             .await
@@ -65,21 +104,49 @@ impl<'client> Client<'client> {
         &self,
         id: impl fmt::Display + fmt::Debug,
         req: requests::Update,
+    ) -> Result<Annotation> {
+        self.update_opt(id, req, RequestOptions::default()).await
+    }
+
+    /// Updates an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    #[instrument(skip(self, request_options))]
+    pub async fn update_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        req: requests::Update,
+        request_options: RequestOptions,
     ) -> Result<Annotation> {
         self.http_client
-            .put(format!("/v2/annotations/{id}"), req)
+            .put(format!("/v2/annotations/{id}"), req, request_options)
             .await?
             .json()
             .await
     }
+
     /// Delets an annotation
     ///
     /// # Errors
     /// If the API call fails
     #[instrument(skip(self))]
     pub async fn delete(&self, id: impl fmt::Display + fmt::Debug) -> Result<()> {
+        self.delete_opt(id, RequestOptions::default()).await
+    }
+
+    /// Delets an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    #[instrument(skip(self, request_options))]
+    pub async fn delete_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        request_options: RequestOptions,
+    ) -> Result<()> {
         self.http_client
-            .delete(format!("/v2/annotations/{id}"))
+            .delete(format!("/v2/annotations/{id}"), request_options)
             .await
     }
 }
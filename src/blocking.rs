@@ -0,0 +1,880 @@
+//! A blocking client for code that can't or doesn't want to use async.
+//!
+//! You're probably looking for the [`Client`]. This module is only available
+//! with the `blocking` feature.
+//!
+//! The blocking client wraps the async [`crate::Client`] and runs it on its
+//! own runtime, so it must not be used from within an async context.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::blocking::Client;
+//! use serde_json::json;
+//!
+//! fn main() -> Result<(), axiom_rs::Error> {
+//!     let client = Client::new()?;
+//!
+//!     client.ingest("my-dataset", vec![json!({ "foo": "bar" })])?;
+//!     let res = client.query("['my-dataset'] | count", None)?;
+//!     println!("{:?}", res.status);
+//!
+//!     Ok(())
+//! }
+//! ```
+use bytes::Bytes;
+use futures::StreamExt;
+use serde::Serialize;
+use std::{convert::TryInto, fmt, fmt::Debug as FmtDebug};
+
+use crate::{
+    annotations::{requests, Annotation},
+    client::Builder,
+    datasets::{
+        ContentEncoding, ContentType, Dataset, DatasetField, FieldUpdate, IngestOptions,
+        IngestStatus, QueryOptions, QueryResult, QueryStreamOptions, Table, TrimDuration,
+        TrimResult,
+    },
+    error::{Error, Result},
+    limits::RecordedLimits,
+    runtime::Executor,
+    stats::Stats,
+    users::User,
+    RequestOptions,
+};
+
+/// The number of events [`Client::ingest_iter`] ingests per request.
+const INGEST_CHUNK_SIZE: usize = 1000;
+
+/// The blocking equivalent of [`crate::Client`].
+///
+/// Create it with [`Client::new`] or configure it with [`Client::builder`]
+/// and [`Builder::build_blocking`].
+///
+/// Calling any method from within an async context panics.
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: crate::Client,
+    executor: Executor,
+}
+
+impl Client {
+    /// Creates a new client. If you want to configure it, use [`Client::builder`].
+    ///
+    /// # Errors
+    /// If the client can not be created
+    pub fn new() -> Result<Self> {
+        Self::builder().build_blocking()
+    }
+
+    /// Create a new client using a builder. Finish it with
+    /// [`Builder::build_blocking`].
+    #[must_use]
+    pub fn builder() -> Builder {
+        crate::Client::builder()
+    }
+
+    pub(crate) fn from_async(inner: crate::Client) -> Result<Self> {
+        let executor = Executor::new().map_err(Error::RuntimeSetup)?;
+        Ok(Self { inner, executor })
+    }
+
+    /// Dataset API
+    #[must_use]
+    pub fn datasets(&self) -> DatasetsClient<'_> {
+        DatasetsClient { client: self }
+    }
+
+    /// Users API
+    #[must_use]
+    pub fn users(&self) -> UsersClient<'_> {
+        UsersClient { client: self }
+    }
+
+    /// Annotations API
+    #[must_use]
+    pub fn annotations(&self) -> AnnotationsClient<'_> {
+        AnnotationsClient { client: self }
+    }
+
+    /// Returns true if the client is configured to use an edge endpoint.
+    #[must_use]
+    pub fn uses_edge(&self) -> bool {
+        self.inner.uses_edge()
+    }
+
+    /// Returns the latest limits reported by the server, see
+    /// [`crate::Client::limits`].
+    #[must_use]
+    pub fn limits(&self) -> RecordedLimits {
+        self.inner.limits()
+    }
+
+    /// Returns a snapshot of the statistics about all requests this client
+    /// made, see [`crate::Client::stats`].
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.inner.stats()
+    }
+
+    /// Get client version.
+    #[must_use]
+    pub fn version(&self) -> &'static str {
+        self.inner.version()
+    }
+
+    /// Executes the given query specified using the Axiom Processing Language
+    /// (APL), see [`crate::Client::query`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn query<S, O>(&self, apl: &S, opts: O) -> Result<QueryResult>
+    where
+        S: ToString + FmtDebug + ?Sized,
+        O: Into<Option<QueryOptions>>,
+    {
+        self.executor.block_on(self.inner.query(apl, opts))
+    }
+
+    /// Like [`Client::query`], but takes [`RequestOptions`], see
+    /// [`crate::Client::query_opt`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn query_opt<S, O>(
+        &self,
+        apl: &S,
+        opts: O,
+        request_options: RequestOptions,
+    ) -> Result<QueryResult>
+    where
+        S: ToString + FmtDebug + ?Sized,
+        O: Into<Option<QueryOptions>>,
+    {
+        self.executor
+            .block_on(self.inner.query_opt(apl, opts, request_options))
+    }
+
+    /// Executes the given query page by page and returns an iterator over the
+    /// resulting tables, see [`crate::Client::query_stream`].
+    ///
+    /// # Errors
+    ///
+    /// The iterator returns an error and ends if a query fails.
+    pub fn query_stream<'a, S, O>(
+        &'a self,
+        apl: &S,
+        opts: O,
+    ) -> impl Iterator<Item = Result<Table>> + 'a
+    where
+        S: ToString + ?Sized,
+        O: Into<Option<QueryStreamOptions>>,
+    {
+        let mut pages = Box::pin(self.inner.query_stream(apl, opts));
+        std::iter::from_fn(move || self.executor.block_on(pages.next()))
+    }
+
+    /// Ingest events into the dataset identified by its id, see
+    /// [`crate::Client::ingest`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
+    pub fn ingest<N, I, E>(&self, dataset_name: N, events: I) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        I: IntoIterator<Item = E>,
+        E: Serialize,
+    {
+        self.executor
+            .block_on(self.inner.ingest(dataset_name, events))
+    }
+
+    /// Like [`Client::ingest`], but takes an [`IngestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
+    pub fn ingest_opt<N, I, E>(
+        &self,
+        dataset_name: N,
+        events: I,
+        ingest_options: IngestOptions,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        I: IntoIterator<Item = E>,
+        E: Serialize,
+    {
+        self.executor
+            .block_on(self.inner.ingest_opt(dataset_name, events, ingest_options))
+    }
+
+    /// Ingest data into the dataset identified by its id, see
+    /// [`crate::Client::ingest_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn ingest_bytes<N, P>(
+        &self,
+        dataset_name: N,
+        payload: P,
+        content_type: ContentType,
+        content_encoding: ContentEncoding,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        P: Into<Bytes>,
+    {
+        self.executor.block_on(self.inner.ingest_bytes(
+            dataset_name,
+            payload,
+            content_type,
+            content_encoding,
+        ))
+    }
+
+    /// Like [`Client::ingest_bytes`], but takes a [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn ingest_bytes_opt<N, P>(
+        &self,
+        dataset_name: N,
+        payload: P,
+        content_type: ContentType,
+        content_encoding: ContentEncoding,
+        request_options: RequestOptions,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        P: Into<Bytes>,
+    {
+        self.executor.block_on(self.inner.ingest_bytes_opt(
+            dataset_name,
+            payload,
+            content_type,
+            content_encoding,
+            request_options,
+        ))
+    }
+
+    /// Ingest events from an iterator into a dataset. Events will be ingested
+    /// in chunks of 1000 items, like [`crate::Client::ingest_stream`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
+    pub fn ingest_iter<N, I, E>(&self, dataset_name: N, events: I) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        I: IntoIterator<Item = E>,
+        E: Serialize,
+    {
+        self.ingest_iter_opt(dataset_name, events, IngestOptions::default())
+    }
+
+    /// Like [`Client::ingest_iter`], but takes an [`IngestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
+    // Taken by value to match the other `_opt` methods.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn ingest_iter_opt<N, I, E>(
+        &self,
+        dataset_name: N,
+        events: I,
+        ingest_options: IngestOptions,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        I: IntoIterator<Item = E>,
+        E: Serialize,
+    {
+        let dataset_name = dataset_name.into();
+        let mut events = events.into_iter();
+        let mut ingest_status = IngestStatus::default();
+        loop {
+            let chunk: Vec<E> = events.by_ref().take(INGEST_CHUNK_SIZE).collect();
+            if chunk.is_empty() {
+                return Ok(ingest_status);
+            }
+            let new_ingest_status =
+                self.ingest_opt(dataset_name.clone(), chunk, ingest_options.clone())?;
+            ingest_status = ingest_status + new_ingest_status;
+        }
+    }
+}
+
+/// The blocking equivalent of [`crate::datasets::Client`].
+#[derive(Debug, Clone)]
+pub struct DatasetsClient<'client> {
+    client: &'client Client,
+}
+
+impl DatasetsClient<'_> {
+    /// Create a dataset with the given name and description.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn create<N, D>(&self, dataset_name: N, description: D) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .create(dataset_name, description),
+        )
+    }
+
+    /// Like [`DatasetsClient::create`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn create_opt<N, D>(
+        &self,
+        dataset_name: N,
+        description: D,
+        request_options: RequestOptions,
+    ) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().create_opt(
+                dataset_name,
+                description,
+                request_options,
+            ))
+    }
+
+    /// Delete the dataset with the given ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn delete<N>(&self, dataset_name: N) -> Result<()>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().delete(dataset_name))
+    }
+
+    /// Like [`DatasetsClient::delete`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn delete_opt<N>(&self, dataset_name: N, request_options: RequestOptions) -> Result<()>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .delete_opt(dataset_name, request_options),
+        )
+    }
+
+    /// Get a dataset by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn get<N>(&self, dataset_name: N) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().get(dataset_name))
+    }
+
+    /// Like [`DatasetsClient::get`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn get_opt<N>(&self, dataset_name: N, request_options: RequestOptions) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .get_opt(dataset_name, request_options),
+        )
+    }
+
+    /// List all fields of the dataset identified by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn fields<N>(&self, dataset_name: N) -> Result<Vec<DatasetField>>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().fields(dataset_name))
+    }
+
+    /// Like [`DatasetsClient::fields`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn fields_opt<N>(
+        &self,
+        dataset_name: N,
+        request_options: RequestOptions,
+    ) -> Result<Vec<DatasetField>>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .fields_opt(dataset_name, request_options),
+        )
+    }
+
+    /// Get a field of the dataset identified by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn field<N, F>(&self, dataset_name: N, field_name: F) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().field(dataset_name, field_name))
+    }
+
+    /// Like [`DatasetsClient::field`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn field_opt<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        request_options: RequestOptions,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().field_opt(
+                dataset_name,
+                field_name,
+                request_options,
+            ))
+    }
+
+    /// Update the metadata of a field of the dataset identified by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn update_field<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        update: FieldUpdate,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(
+                self.client
+                    .inner
+                    .datasets()
+                    .update_field(dataset_name, field_name, update),
+            )
+    }
+
+    /// Like [`DatasetsClient::update_field`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn update_field_opt<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        update: FieldUpdate,
+        request_options: RequestOptions,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().update_field_opt(
+                dataset_name,
+                field_name,
+                update,
+                request_options,
+            ))
+    }
+
+    /// List all available datasets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn list(&self) -> Result<Vec<Dataset>> {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().list())
+    }
+
+    /// Like [`DatasetsClient::list`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn list_opt(&self, request_options: RequestOptions) -> Result<Vec<Dataset>> {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().list_opt(request_options))
+    }
+
+    /// Trim the dataset identified by its id to a given length, see
+    /// [`crate::datasets::Client::trim`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the duration is out of range or the HTTP request
+    /// or JSON deserializing fails.
+    pub fn trim<N, D>(&self, dataset_name: N, max_duration: D) -> Result<TrimResult>
+    where
+        N: Into<String> + FmtDebug,
+        D: TryInto<TrimDuration, Error = Error> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .trim(dataset_name, max_duration),
+        )
+    }
+
+    /// Like [`DatasetsClient::trim`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the duration is out of range or the HTTP request
+    /// or JSON deserializing fails.
+    pub fn trim_opt<N, D>(
+        &self,
+        dataset_name: N,
+        max_duration: D,
+        request_options: RequestOptions,
+    ) -> Result<TrimResult>
+    where
+        N: Into<String> + FmtDebug,
+        D: TryInto<TrimDuration, Error = Error> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().trim_opt(
+                dataset_name,
+                max_duration,
+                request_options,
+            ))
+    }
+
+    /// Update a dataset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn update<N, D>(&self, dataset_name: N, new_description: D) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .datasets()
+                .update(dataset_name, new_description),
+        )
+    }
+
+    /// Like [`DatasetsClient::update`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn update_opt<N, D>(
+        &self,
+        dataset_name: N,
+        new_description: D,
+        request_options: RequestOptions,
+    ) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.client
+            .executor
+            .block_on(self.client.inner.datasets().update_opt(
+                dataset_name,
+                new_description,
+                request_options,
+            ))
+    }
+}
+
+/// The blocking equivalent of [`crate::users::Client`].
+#[derive(Debug, Clone)]
+pub struct UsersClient<'client> {
+    client: &'client Client,
+}
+
+impl UsersClient<'_> {
+    /// Retrieve the authenticated user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn current(&self) -> Result<User> {
+        self.client
+            .executor
+            .block_on(self.client.inner.users().current())
+    }
+
+    /// Like [`UsersClient::current`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    pub fn current_opt(&self, request_options: RequestOptions) -> Result<User> {
+        self.client
+            .executor
+            .block_on(self.client.inner.users().current_opt(request_options))
+    }
+}
+
+/// The blocking equivalent of [`crate::annotations::Client`].
+#[derive(Debug, Clone)]
+pub struct AnnotationsClient<'client> {
+    client: &'client Client,
+}
+
+impl AnnotationsClient<'_> {
+    /// Creates an annotation
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn create(&self, req: requests::Create) -> Result<Annotation> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().create(req))
+    }
+
+    /// Creates an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn create_opt(
+        &self,
+        req: requests::Create,
+        request_options: RequestOptions,
+    ) -> Result<Annotation> {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .annotations()
+                .create_opt(req, request_options),
+        )
+    }
+
+    /// Gets an annotation
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn get(&self, id: impl fmt::Display + fmt::Debug) -> Result<Annotation> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().get(id))
+    }
+
+    /// Gets an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn get_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        request_options: RequestOptions,
+    ) -> Result<Annotation> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().get_opt(id, request_options))
+    }
+
+    /// Lists annotations
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn list(&self, req: requests::List) -> Result<Vec<Annotation>> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().list(req))
+    }
+
+    /// Lists annotations with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn list_opt(
+        &self,
+        req: requests::List,
+        request_options: RequestOptions,
+    ) -> Result<Vec<Annotation>> {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .annotations()
+                .list_opt(req, request_options),
+        )
+    }
+
+    /// Updates an annotation
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn update(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        req: requests::Update,
+    ) -> Result<Annotation> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().update(id, req))
+    }
+
+    /// Updates an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn update_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        req: requests::Update,
+        request_options: RequestOptions,
+    ) -> Result<Annotation> {
+        self.client
+            .executor
+            .block_on(
+                self.client
+                    .inner
+                    .annotations()
+                    .update_opt(id, req, request_options),
+            )
+    }
+
+    /// Deletes an annotation
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn delete(&self, id: impl fmt::Display + fmt::Debug) -> Result<()> {
+        self.client
+            .executor
+            .block_on(self.client.inner.annotations().delete(id))
+    }
+
+    /// Deletes an annotation with request options
+    ///
+    /// # Errors
+    /// If the API call fails
+    pub fn delete_opt(
+        &self,
+        id: impl fmt::Display + fmt::Debug,
+        request_options: RequestOptions,
+    ) -> Result<()> {
+        self.client.executor.block_on(
+            self.client
+                .inner
+                .annotations()
+                .delete_opt(id, request_options),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use super::Client;
+
+    #[test]
+    fn test_blocking_client() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let list_mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(200).json_body(json!([]));
+        });
+        let ingest_mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build_blocking()?;
+
+        assert!(client.datasets().list()?.is_empty());
+        let status = client.ingest("test", vec![json!({"foo": "bar"}), json!({"foo": "baz"})])?;
+        assert_eq!(status.ingested, 2);
+
+        list_mock.assert_hits(1);
+        ingest_mock.assert_hits(1);
+        Ok(())
+    }
+}
@@ -2,13 +2,24 @@
 #[cfg(feature = "async-std")]
 use async_std::task::spawn_blocking;
 use bytes::Bytes;
-use flate2::{write::GzEncoder, Compression};
-use futures::Stream;
+use flate2::{write::GzEncoder, Compression as GzCompression};
+use futures::{
+    channel::oneshot,
+    future::{self, FutureExt, Shared},
+    stream, Stream, TryStreamExt,
+};
 use reqwest::header;
 use serde::Serialize;
 use std::{
-    env, fmt::Debug as FmtDebug, io::Write, result::Result as StdResult,
-    time::Duration as StdDuration,
+    env,
+    fmt::Debug as FmtDebug,
+    io::Write,
+    result::Result as StdResult,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration as StdDuration, Instant},
 };
 #[cfg(feature = "tokio")]
 use tokio::task::spawn_blocking;
@@ -18,12 +29,20 @@ use tracing::instrument;
 use crate::{
     annotations,
     datasets::{
-        self, ContentEncoding, ContentType, IngestStatus, Query, QueryOptions, QueryParams,
-        QueryResult,
+        self, Compression, ContentEncoding, ContentType, FailedChunk, IngestOptions, IngestStatus,
+        IngestStreamOptions, OnChunkFailure, Query, QueryOptions, QueryParams, QueryResult,
+        QueryStreamOptions, Table,
     },
     error::{Error, Result},
-    http::{self, HeaderMap},
-    is_personal_token, users,
+    http::{self, HeaderMap, Transport},
+    ingester::{self, Batch, BatchIngester, BatchOptions},
+    is_personal_token,
+    limits::RecordedLimits,
+    middleware::Middleware,
+    retry::RetryPolicy,
+    stats::Stats,
+    trace_context::{self, TraceContext},
+    users,
 };
 
 /// API URL is the URL for the Axiom Cloud API.
@@ -83,11 +102,131 @@ impl PathStyle {
     }
 }
 
-/// Request options that can be passed to some handlers.
-#[derive(Debug, Default)]
+/// Request options that can be passed to the `_opt` variants of all
+/// handlers.
+///
+/// # Examples
+/// ```no_run
+/// use axiom_rs::{retry::RetryPolicy, CancellationToken, Client, RequestOptions};
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new()?;
+///
+/// let token = CancellationToken::new();
+/// let options = RequestOptions::default()
+///     .with_timeout(Duration::from_secs(5))
+///     .with_retry_policy(RetryPolicy::disabled())
+///     .with_cancellation_token(token.clone());
+/// let datasets = client.datasets().list_opt(options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
 pub struct RequestOptions {
     /// Additional headers for the request.
     pub additional_headers: HeaderMap,
+    /// The timeout of every attempt of the request. Defaults to the timeout
+    /// configured on the client for the kind of request.
+    pub timeout: Option<StdDuration>,
+    /// The retry policy for this request. Defaults to the retry policy
+    /// configured on the client.
+    pub retry_policy: Option<RetryPolicy>,
+    /// A token to cancel the request with. A cancelled request, including
+    /// any pending retries and reading the response body, fails with
+    /// [`Error::Cancelled`].
+    pub cancellation_token: Option<CancellationToken>,
+}
+
+impl RequestOptions {
+    /// Adds a header to the request, replacing any previous value.
+    #[must_use]
+    pub fn with_header(mut self, name: header::HeaderName, value: header::HeaderValue) -> Self {
+        self.additional_headers.insert(name, value);
+        self
+    }
+
+    /// Sets the timeout of every attempt of the request.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: StdDuration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the retry policy for this request.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Sets the token to cancel the request with.
+    #[must_use]
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+}
+
+/// A token to cancel one or more requests with.
+///
+/// Clones share their state, so cancelling any of them cancels all requests
+/// the token was passed to.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Debug)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    sender: Mutex<Option<oneshot::Sender<()>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that isn't cancelled yet.
+    #[must_use]
+    pub fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        Self {
+            inner: Arc::new(CancellationState {
+                cancelled: AtomicBool::new(false),
+                sender: Mutex::new(Some(sender)),
+                receiver: receiver.shared(),
+            }),
+        }
+    }
+
+    /// Cancels all requests using this token.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        // Dropping the sender wakes up everyone waiting on the receiver.
+        self.inner
+            .sender
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+    }
+
+    /// Returns true if the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the token is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        // The sender is never used, so this only resolves once it's dropped.
+        let _ = self.inner.receiver.clone().await;
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// The client is the entrypoint of the whole SDK.
@@ -131,6 +270,8 @@ pub struct Client {
     path_style: PathStyle,
     /// Whether a personal token is being used.
     is_personal_token: bool,
+    /// The default compression for ingested events.
+    compression: Compression,
 }
 
 impl Client {
@@ -186,6 +327,37 @@ impl Client {
         self.path_style.is_edge()
     }
 
+    /// Returns the latest rate, query and ingest limits reported by the
+    /// server.
+    ///
+    /// Limits are recorded from every response, successful or not. A limit
+    /// is `None` until a response reporting it has been received.
+    #[must_use]
+    pub fn limits(&self) -> RecordedLimits {
+        // Both HTTP clients share the same limits.
+        self.api_http.recorded_limits()
+    }
+
+    /// Returns a snapshot of the statistics about all requests this client
+    /// made since it was built, see [`crate::stats`].
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        // Both HTTP clients share the same stats.
+        self.api_http.stats()
+    }
+
+    /// Creates a [`BatchIngester`] that buffers single events and ingests them
+    /// in batches from a background task.
+    ///
+    /// This needs to be called from within a runtime.
+    #[must_use]
+    pub fn batch_ingester<O>(&self, opts: O) -> BatchIngester
+    where
+        O: Into<Option<BatchOptions>>,
+    {
+        BatchIngester::new(self.clone(), opts.into().unwrap_or_default())
+    }
+
     /// Get client version.
     #[must_use]
     pub fn version(&self) -> &'static str {
@@ -201,17 +373,46 @@ impl Client {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self, opts))]
     pub async fn query<S, O>(&self, apl: &S, opts: O) -> Result<QueryResult>
+    where
+        S: ToString + FmtDebug + ?Sized,
+        O: Into<Option<QueryOptions>>,
+    {
+        self.query_opt(apl, opts, RequestOptions::default()).await
+    }
+
+    /// Like [`Client::query`], but takes [`RequestOptions`]. A timeout set in
+    /// the [`QueryOptions`] takes precedence over the one in the
+    /// [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, opts, request_options))]
+    pub async fn query_opt<S, O>(
+        &self,
+        apl: &S,
+        opts: O,
+        mut request_options: RequestOptions,
+    ) -> Result<QueryResult>
     where
         S: ToString + FmtDebug + ?Sized,
         O: Into<Option<QueryOptions>>,
     {
         let opts: QueryOptions = opts.into().unwrap_or_default();
         let query_params = QueryParams::from(&opts);
+        if opts.timeout.is_some() {
+            request_options.timeout = opts.timeout;
+        }
+        let strict = opts.strict;
         let req = Query::new(apl, opts);
 
         let query_params = serde_qs::to_string(&query_params)?;
         let path = self.path_style.query_path(&query_params);
-        let resp = self.edge_http.post(path, &req).await?;
+        let started = Instant::now();
+        let resp = self
+            .edge_http
+            .post_query(path, &req, request_options)
+            .await?;
 
         let saved_query_id = resp
             .headers()
@@ -232,9 +433,125 @@ impl Client {
         result.saved_query_id = saved_query_id;
         result.trace_id = trace_id;
 
+        // The server reports the elapsed time in microseconds.
+        let server_time = StdDuration::from_micros(result.status.elapsed_time);
+        self.edge_http.update_stats(|stats| {
+            stats.query_server_time.record(server_time);
+            stats.query_wall_time.record(started.elapsed());
+        });
+
+        if let Some(priority) = strict {
+            let messages: Vec<_> = result.messages_at_least(priority).cloned().collect();
+            if !messages.is_empty() {
+                return Err(Error::QueryMessages { priority, messages });
+            }
+        }
+
         Ok(result)
     }
 
+    /// Executes the given APL query page by page and returns a stream of the
+    /// resulting tables, one per page.
+    ///
+    /// Every page is limited to `page_size` rows by appending a `limit` to the
+    /// query. Follow-up queries start at the cursor of the last row of the
+    /// previous page until a page has less than `page_size` rows or
+    /// `max_pages` is reached. The query should sort events by `_time`, e.g.
+    /// `['my-dataset'] | sort by _time desc`, aggregations can't be paginated.
+    /// The stream also ends if a page doesn't move past the cursor it started
+    /// at.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::Client;
+    /// use futures::TryStreamExt;
+    ///
+    /// # async fn run() -> Result<(), axiom_rs::Error> {
+    /// let client = Client::new()?;
+    /// let mut pages = Box::pin(client.query_stream("['my-dataset'] | sort by _time desc", None));
+    /// while let Some(table) = pages.try_next().await? {
+    ///     println!("Got {} rows", table.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The stream returns an error and ends if a query fails.
+    pub fn query_stream<'a, S, O>(
+        &'a self,
+        apl: &S,
+        opts: O,
+    ) -> impl Stream<Item = Result<Table>> + 'a
+    where
+        S: ToString + ?Sized,
+        O: Into<Option<QueryStreamOptions>>,
+    {
+        let opts: QueryStreamOptions = opts.into().unwrap_or_default();
+        let page_size = opts.page_size;
+        let max_pages = opts.max_pages;
+        let request_options = opts.request_options;
+        let apl = format!("{}\n| limit {page_size}", apl.to_string());
+
+        // The state holds the options of the next query and the page number,
+        // it's `None` once there are no more pages.
+        let state = Some((opts.query_options, 0));
+        stream::try_unfold(state, move |state| {
+            let apl = apl.clone();
+            let request_options = request_options.clone();
+            async move {
+                let (query_options, page) = match state {
+                    Some((_, page)) if max_pages.map_or(false, |max_pages| page >= max_pages) => {
+                        return Ok(None)
+                    }
+                    Some(state) => state,
+                    None => return Ok(None),
+                };
+
+                let result = self
+                    .query_opt(
+                        &apl,
+                        QueryOptions {
+                            include_cursor_field: true,
+                            ..query_options.clone()
+                        },
+                        request_options,
+                    )
+                    .await?;
+                let table = match result.tables.into_iter().next() {
+                    Some(table) if !table.is_empty() => table,
+                    _ => return Ok(None),
+                };
+
+                let cursor = table.iter().last().and_then(|row| {
+                    row.get_field("_cursor")
+                        .and_then(serde_json::Value::as_str)
+                        .map(ToString::to_string)
+                });
+                // A page ending at the cursor it started after doesn't move
+                // forward, e.g. because the query ignores the cursor. It
+                // repeats the previous page, so stop instead of looping.
+                if !query_options.include_cursor
+                    && cursor.is_some()
+                    && cursor == query_options.cursor
+                {
+                    return Ok(None);
+                }
+                let cursor = cursor.filter(|_| table.len() >= page_size);
+                let next = cursor.map(|cursor| {
+                    let query_options = QueryOptions {
+                        cursor: Some(cursor),
+                        include_cursor: false,
+                        ..query_options
+                    };
+                    (query_options, page + 1)
+                });
+                Ok(Some((table, next)))
+            }
+        })
+    }
+
     /// Ingest events into the dataset identified by its id.
     /// Restrictions for field names (JSON object keys) can be reviewed here:
     /// <https://www.axiom.co/docs/usage/field-restrictions>.
@@ -245,6 +562,29 @@ impl Client {
     /// request or JSON deserializing fails.
     #[instrument(skip(self, events))]
     pub async fn ingest<N, I, E>(&self, dataset_name: N, events: I) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        I: IntoIterator<Item = E>,
+        E: Serialize,
+    {
+        self.ingest_opt(dataset_name, events, IngestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::ingest`], but takes an [`IngestOptions`], which allows
+    /// you to override the compression and request options for this request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
+    #[instrument(skip(self, events))]
+    pub async fn ingest_opt<N, I, E>(
+        &self,
+        dataset_name: N,
+        events: I,
+        ingest_options: IngestOptions,
+    ) -> Result<IngestStatus>
     where
         N: Into<String> + FmtDebug,
         I: IntoIterator<Item = E>,
@@ -255,21 +595,44 @@ impl Client {
             .map(|event| serde_json::to_vec(&event).map_err(Error::Serialize))
             .collect();
         let json_payload = json_lines?.join(&b"\n"[..]);
-        let payload = spawn_blocking(move || {
-            let mut gzip_payload = GzEncoder::new(Vec::new(), Compression::default());
-            gzip_payload.write_all(&json_payload)?;
-            gzip_payload.finish()
-        })
-        .await;
-        #[cfg(feature = "tokio")]
-        let payload = payload.map_err(Error::JoinError)?;
-        let payload = payload.map_err(Error::Encoding)?;
+        self.ingest_ndjson(dataset_name, json_payload, ingest_options)
+            .await
+    }
+
+    /// Compresses the given NDJSON payload and ingests it. Uses the client's
+    /// compression if none is given.
+    pub(crate) async fn ingest_ndjson<N>(
+        &self,
+        dataset_name: N,
+        json_payload: Vec<u8>,
+        ingest_options: IngestOptions,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        let compression = ingest_options.compression.unwrap_or(self.compression);
+        let uncompressed_len = json_payload.len() as u64;
+        let payload = match compression {
+            Compression::Identity => json_payload,
+            compression => {
+                let payload = spawn_blocking(move || compress(compression, &json_payload)).await;
+                #[cfg(feature = "tokio")]
+                let payload = payload.map_err(Error::JoinError)?;
+                payload.map_err(Error::Encoding)?
+            }
+        };
+        let compressed_len = payload.len() as u64;
+        self.edge_http.update_stats(|stats| {
+            stats.ingest_bytes_uncompressed += uncompressed_len;
+            stats.ingest_bytes_compressed += compressed_len;
+        });
 
-        self.ingest_bytes(
+        self.ingest_bytes_opt(
             dataset_name,
             payload,
             ContentType::NdJson,
-            ContentEncoding::Gzip,
+            compression.content_encoding(),
+            ingest_options.request_options,
         )
         .await
     }
@@ -318,7 +681,7 @@ impl Client {
         payload: P,
         content_type: ContentType,
         content_encoding: ContentEncoding,
-        request_options: RequestOptions,
+        mut request_options: RequestOptions,
     ) -> Result<IngestStatus>
     where
         N: Into<String> + FmtDebug,
@@ -329,38 +692,38 @@ impl Client {
             return Err(Error::PersonalTokenNotSupportedForEdge);
         }
 
-        let mut headers = HeaderMap::new();
-
-        // Add headers from request options
-        for (key, value) in request_options.additional_headers {
-            if let Some(key) = key {
-                headers.insert(key, value);
-            }
-        }
-
         // Add content-type, content-encoding
+        let headers = &mut request_options.additional_headers;
         headers.insert(header::CONTENT_TYPE, content_type.into());
         headers.insert(header::CONTENT_ENCODING, content_encoding.into());
 
         let dataset_name = dataset_name.into();
         let path = self.path_style.ingest_path(&dataset_name);
 
-        self.edge_http
-            .post_bytes(path, payload, headers)
+        let ingest_status: IngestStatus = self
+            .edge_http
+            .post_bytes(path, payload, request_options)
             .await?
             .json()
-            .await
+            .await?;
+        self.edge_http.update_stats(|stats| {
+            stats.events_ingested += ingest_status.ingested;
+            stats.events_failed += ingest_status.failed;
+        });
+        Ok(ingest_status)
     }
 
     /// Ingest a stream of events into a dataset. Events will be ingested in
-    /// chunks of 1000 items. If ingestion of a chunk fails, it will be retried
-    /// with a backoff.
+    /// chunks of up to 1000 items or 1 MiB, or whatever arrived within a
+    /// second. If ingestion of a chunk fails, it will be retried with a
+    /// backoff.
     /// Restrictions for field names (JSON object keys) can be reviewed here:
     /// <https://www.axiom.co/docs/usage/field-restrictions>.
     ///
     /// # Errors
     ///
-    /// Returns an error if the HTTP request or JSON deserializing fails.
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails.
     #[instrument(skip(self, stream))]
     pub async fn ingest_stream<N, S, E>(&self, dataset_name: N, stream: S) -> Result<IngestStatus>
     where
@@ -368,21 +731,46 @@ impl Client {
         S: Stream<Item = E> + Send + Sync + 'static,
         E: Serialize,
     {
-        let dataset_name = dataset_name.into();
-        let mut chunks = Box::pin(stream.chunks_timeout(1000, StdDuration::from_secs(1)));
-        let mut ingest_status = IngestStatus::default();
-        while let Some(events) = chunks.next().await {
-            let new_ingest_status = self.ingest(dataset_name.clone(), events).await?;
-            ingest_status = ingest_status + new_ingest_status;
-        }
-        Ok(ingest_status)
+        self.ingest_stream_opt(dataset_name, stream, IngestStreamOptions::default())
+            .await
+    }
+
+    /// Like [`Client::ingest_stream`], but takes an [`IngestStreamOptions`],
+    /// which allows you to configure the chunking, the number of concurrent
+    /// requests and the compression for this stream. An [`IngestOptions`]
+    /// only overrides the compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the events cannot be serialized or if the HTTP
+    /// request or JSON deserializing fails. With
+    /// [`OnChunkFailure::Collect`], returns [`Error::PartialIngest`] if any
+    /// chunk failed to ingest.
+    #[instrument(skip(self, stream, options))]
+    pub async fn ingest_stream_opt<N, S, E, O>(
+        &self,
+        dataset_name: N,
+        stream: S,
+        options: O,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        S: Stream<Item = E> + Send + Sync + 'static,
+        E: Serialize,
+        O: Into<IngestStreamOptions>,
+    {
+        let options = options.into();
+        let batches = ingester::chunks(stream.map(Ok), &options);
+        self.ingest_batches(dataset_name.into(), batches, &options)
+            .await
     }
 
     /// Like [`Client::ingest_stream`], but takes a stream that contains results.
     ///
     /// # Errors
     ///
-    /// Returns an error if the HTTP request or JSON deserializing fails.
+    /// Returns an error if the stream returns an error, the events cannot be
+    /// serialized or if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self, stream))]
     pub async fn try_ingest_stream<N, S, I, E>(
         &self,
@@ -395,20 +783,112 @@ impl Client {
         I: Serialize,
         E: std::error::Error + Send + Sync + 'static,
     {
-        let dataset_name = dataset_name.into();
-        let mut chunks = Box::pin(stream.chunks_timeout(1000, StdDuration::from_secs(1)));
-        let mut ingest_status = IngestStatus::default();
-        while let Some(events) = chunks.next().await {
-            let events: StdResult<Vec<I>, E> = events.into_iter().collect();
-            match events {
-                Ok(events) => {
-                    let new_ingest_status = self.ingest(dataset_name.clone(), events).await?;
-                    ingest_status = ingest_status + new_ingest_status;
+        self.try_ingest_stream_opt(dataset_name, stream, IngestStreamOptions::default())
+            .await
+    }
+
+    /// Like [`Client::try_ingest_stream`], but takes an
+    /// [`IngestStreamOptions`], which allows you to configure the chunking,
+    /// the number of concurrent requests and the compression for this stream.
+    /// An [`IngestOptions`] only overrides the compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream returns an error, the events cannot be
+    /// serialized or if the HTTP request or JSON deserializing fails. With
+    /// [`OnChunkFailure::Collect`], returns [`Error::PartialIngest`] if any
+    /// chunk failed to ingest.
+    #[instrument(skip(self, stream, options))]
+    pub async fn try_ingest_stream_opt<N, S, I, E, O>(
+        &self,
+        dataset_name: N,
+        stream: S,
+        options: O,
+    ) -> Result<IngestStatus>
+    where
+        N: Into<String> + FmtDebug,
+        S: Stream<Item = StdResult<I, E>> + Send + Sync + 'static,
+        I: Serialize,
+        E: std::error::Error + Send + Sync + 'static,
+        O: Into<IngestStreamOptions>,
+    {
+        let options = options.into();
+        let stream = stream.map(|res| res.map_err(|e| Error::IngestStreamError(Box::new(e))));
+        let batches = ingester::chunks(stream, &options);
+        self.ingest_batches(dataset_name.into(), batches, &options)
+            .await
+    }
+
+    /// Ingests the batches of a stream, up to `options.concurrency` at the
+    /// same time, and handles failed ones as configured in the options.
+    async fn ingest_batches<S>(
+        &self,
+        dataset_name: String,
+        batches: S,
+        options: &IngestStreamOptions,
+    ) -> Result<IngestStatus>
+    where
+        S: Stream<Item = Result<Batch>>,
+    {
+        let dataset_name = &dataset_name;
+        let ingest_options = &options.ingest_options;
+        let abort = matches!(options.on_chunk_failure, OnChunkFailure::Abort);
+        let (ingest_status, failed) = batches
+            .map_ok(|batch| async move {
+                let res = self
+                    .ingest_ndjson(dataset_name, batch.payload(), ingest_options.clone())
+                    .await;
+                match res {
+                    Ok(ingest_status) => Ok(Ok(ingest_status)),
+                    Err(e) if abort => Err(e),
+                    Err(error) => Ok(Err(FailedChunk {
+                        events: batch.into_events(),
+                        error,
+                    })),
                 }
-                Err(e) => return Err(Error::IngestStreamError(Box::new(e))),
-            }
+            })
+            .try_buffer_unordered(options.concurrency.max(1))
+            .try_fold(
+                (IngestStatus::default(), Vec::new()),
+                |(ingest_status, mut failed), res| {
+                    let ingest_status = match res {
+                        Ok(new_ingest_status) => ingest_status + new_ingest_status,
+                        Err(chunk) => {
+                            if let OnChunkFailure::Callback(callback) = &options.on_chunk_failure {
+                                callback(chunk);
+                            } else {
+                                failed.push(chunk);
+                            }
+                            ingest_status
+                        }
+                    };
+                    future::ok((ingest_status, failed))
+                },
+            )
+            .await?;
+
+        if failed.is_empty() {
+            Ok(ingest_status)
+        } else {
+            Err(Error::PartialIngest {
+                status: Box::new(ingest_status),
+                failed,
+            })
         }
-        Ok(ingest_status)
+    }
+}
+
+/// Compresses the payload with the given compression.
+fn compress(compression: Compression, payload: &[u8]) -> std::io::Result<Vec<u8>> {
+    match compression {
+        Compression::Identity => Ok(payload.to_vec()),
+        Compression::Gzip(level) => {
+            let mut encoder = GzEncoder::new(Vec::new(), GzCompression::new(level));
+            encoder.write_all(payload)?;
+            encoder.finish()
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => zstd::encode_all(payload, level),
     }
 }
 
@@ -419,6 +899,13 @@ pub struct Builder {
     edge_url: Option<String>,
     token: Option<String>,
     org_id: Option<String>,
+    compression: Compression,
+    retry_policy: RetryPolicy,
+    limit_throttling: bool,
+    transport: Option<Arc<dyn Transport>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    connect_timeout: StdDuration,
+    timeouts: http::Timeouts,
 }
 
 impl Builder {
@@ -430,6 +917,13 @@ impl Builder {
             edge_url: None,
             token: None,
             org_id: None,
+            compression: Compression::default(),
+            retry_policy: RetryPolicy::default(),
+            limit_throttling: false,
+            transport: None,
+            middleware: Vec::new(),
+            connect_timeout: StdDuration::from_secs(10),
+            timeouts: http::Timeouts::default(),
         }
     }
 
@@ -507,6 +1001,167 @@ impl Builder {
         self
     }
 
+    /// Set the compression used for ingesting events. Defaults to gzip.
+    ///
+    /// This applies to [`Client::ingest`] and the stream ingest methods and
+    /// can be overridden per call with [`IngestOptions`].
+    #[must_use]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set the policy used to retry failed requests.
+    ///
+    /// By default requests failing without a response are retried for up to
+    /// 30 seconds, error responses and exceeded limits are not retried.
+    /// Use [`RetryPolicy::disabled`] to turn retries off.
+    #[must_use]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Wait for exceeded limits to reset before sending a request, instead
+    /// of sending it and failing with a limit error. Disabled by default.
+    ///
+    /// The client only knows about a limit once the server reported it, see
+    /// [`Client::limits`]. Rate limits delay all requests, query and ingest
+    /// limits only delay queries and ingest requests respectively. Keep in
+    /// mind that a request might be delayed for a long time, depending on
+    /// your plan's limits.
+    #[must_use]
+    pub fn with_limit_throttling(mut self, limit_throttling: bool) -> Self {
+        self.limit_throttling = limit_throttling;
+        self
+    }
+
+    /// Set the timeout for establishing a connection. Defaults to 10 seconds.
+    ///
+    /// This only applies to the HTTP client created by default, configure
+    /// your own when using [`Builder::with_http_client`].
+    #[must_use]
+    pub fn with_connect_timeout(mut self, timeout: StdDuration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set the timeout of all requests, see the kind-specific methods like
+    /// [`Builder::with_query_timeout`] for the defaults.
+    #[must_use]
+    pub fn with_timeout(self, timeout: StdDuration) -> Self {
+        self.with_query_timeout(timeout)
+            .with_ingest_timeout(timeout)
+            .with_management_timeout(timeout)
+    }
+
+    /// Set the timeout of queries. Defaults to 2 minutes.
+    ///
+    /// It can be overridden per query with [`QueryOptions::timeout`].
+    #[must_use]
+    pub fn with_query_timeout(mut self, timeout: StdDuration) -> Self {
+        self.timeouts.query = timeout;
+        self
+    }
+
+    /// Set the timeout of ingest requests. Defaults to 1 minute.
+    ///
+    /// It can be overridden per request with [`RequestOptions::timeout`].
+    #[must_use]
+    pub fn with_ingest_timeout(mut self, timeout: StdDuration) -> Self {
+        self.timeouts.ingest = timeout;
+        self
+    }
+
+    /// Set the timeout of all other requests, like managing datasets.
+    /// Defaults to 10 seconds.
+    #[must_use]
+    pub fn with_management_timeout(mut self, timeout: StdDuration) -> Self {
+        self.timeouts.management = timeout;
+        self
+    }
+
+    /// Use the given [`reqwest::Client`] to send requests, e.g. to configure
+    /// a proxy, custom root certificates or connection pooling.
+    ///
+    /// The client is used as is, only the request timeouts configured on
+    /// this builder are applied. Authentication and the other headers the API
+    /// needs are added to every request.
+    #[must_use]
+    pub fn with_http_client(self, http_client: reqwest::Client) -> Self {
+        self.with_transport(http_client)
+    }
+
+    /// Use the given [`Transport`] to send requests. Both API and edge
+    /// requests go through it.
+    ///
+    /// See [`Builder::with_http_client`] if you just want to configure the
+    /// underlying [`reqwest::Client`].
+    #[must_use]
+    pub fn with_transport<T: Transport>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Add a [`Middleware`] that every attempt of every request passes
+    /// through, for both API and edge requests.
+    ///
+    /// Middleware runs in the order it was added: the first one sees the
+    /// request first and the response last.
+    #[must_use]
+    pub fn with_middleware<M: Middleware>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Add the trace context returned by `provider` to every request as
+    /// `traceparent` and `tracestate` headers, see [`crate::trace_context`].
+    ///
+    /// The provider is called for every attempt of a request, from within
+    /// the task making the request. If it returns `None`, no headers are
+    /// added.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::{trace_context::TraceContext, Client};
+    ///
+    /// # fn main() -> Result<(), axiom_rs::Error> {
+    /// let client = Client::builder()
+    ///     .with_trace_context(|| {
+    ///         TraceContext::parse(
+    ///             "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+    ///             None,
+    ///         )
+    ///     })
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_trace_context<F>(self, provider: F) -> Self
+    where
+        F: Fn() -> Option<TraceContext> + Send + Sync + 'static,
+    {
+        self.with_middleware(trace_context::Propagation::new(provider))
+    }
+
+    /// Add the trace context of the current [`tracing`] span to every
+    /// request, see [`TraceContext::current`].
+    #[cfg(feature = "tracing")]
+    #[must_use]
+    pub fn with_trace_context_propagation(self) -> Self {
+        self.with_trace_context(TraceContext::current)
+    }
+
+    /// Build a [`blocking::Client`](crate::blocking::Client).
+    ///
+    /// # Errors
+    /// If the client or its runtime can not be built
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::blocking::Client> {
+        crate::blocking::Client::from_async(self.build()?)
+    }
+
     /// Build the client.
     ///
     /// # Errors
@@ -587,8 +1242,31 @@ impl Builder {
         } else {
             Some(org_id)
         };
-        let api_http = http::Client::new(api_url.clone(), token.clone(), org_id_opt.clone())?;
-        let edge_http = http::Client::new(path_style.url(), token, org_id_opt)?;
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => Arc::new(
+                reqwest::Client::builder()
+                    .connect_timeout(self.connect_timeout)
+                    .build()
+                    .map_err(Error::HttpClientSetup)?,
+            ),
+        };
+        let config = http::Config {
+            transport,
+            timeouts: self.timeouts,
+            retry_policy: self.retry_policy,
+            limits: Arc::new(Mutex::new(RecordedLimits::default())),
+            limit_throttling: self.limit_throttling,
+            middleware: self.middleware,
+            stats: Arc::new(Mutex::new(Stats::default())),
+        };
+        let api_http = http::Client::new(
+            api_url.clone(),
+            token.clone(),
+            org_id_opt.clone(),
+            config.clone(),
+        )?;
+        let edge_http = http::Client::new(path_style.url(), token, org_id_opt, config)?;
 
         Ok(Client {
             api_http,
@@ -596,6 +1274,7 @@ impl Builder {
             api_url,
             path_style,
             is_personal_token,
+            compression: self.compression,
         })
     }
 }
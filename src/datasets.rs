@@ -32,4 +32,6 @@ mod client;
 mod model;
 
 pub use client::Client;
+#[cfg(feature = "blocking")]
+pub(crate) use client::Duration as TrimDuration;
 pub use model::*;
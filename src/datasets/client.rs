@@ -1,11 +1,16 @@
 #[allow(deprecated)]
 use crate::{
-    datasets::model::{Dataset, DatasetCreateRequest, DatasetUpdateRequest, Info},
+    datasets::model::{
+        Dataset, DatasetCreateRequest, DatasetField, DatasetUpdateRequest, FieldUpdate, Info,
+        TrimRequest, TrimResult,
+    },
     error::{Error, Result},
-    http,
+    http, RequestOptions,
 };
 use std::{
-    convert::TryFrom, fmt::Debug as FmtDebug, result::Result as StdResult,
+    convert::{TryFrom, TryInto},
+    fmt::Debug as FmtDebug,
+    result::Result as StdResult,
     time::Duration as StdDuration,
 };
 use tracing::instrument;
@@ -31,6 +36,26 @@ impl<'client> Client<'client> {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self))]
     pub async fn create<N, D>(&self, dataset_name: N, description: D) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.create_opt(dataset_name, description, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::create`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn create_opt<N, D>(
+        &self,
+        dataset_name: N,
+        description: D,
+        request_options: RequestOptions,
+    ) -> Result<Dataset>
     where
         N: Into<String> + FmtDebug,
         D: Into<String> + FmtDebug,
@@ -40,7 +65,7 @@ impl<'client> Client<'client> {
             description: description.into(),
         };
         self.http_client
-            .post("/v1/datasets", &req)
+            .post("/v1/datasets", &req, request_options)
             .await?
             .json()
             .await
@@ -53,11 +78,32 @@ impl<'client> Client<'client> {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self))]
     pub async fn delete<N>(&self, dataset_name: N) -> Result<()>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.delete_opt(dataset_name, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::delete`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn delete_opt<N>(
+        &self,
+        dataset_name: N,
+        request_options: RequestOptions,
+    ) -> Result<()>
     where
         N: Into<String> + FmtDebug,
     {
         self.http_client
-            .delete(format!("/v1/datasets/{}", dataset_name.into()))
+            .delete(
+                format!("/v1/datasets/{}", dataset_name.into()),
+                request_options,
+            )
             .await
     }
 
@@ -70,9 +116,186 @@ impl<'client> Client<'client> {
     pub async fn get<N>(&self, dataset_name: N) -> Result<Dataset>
     where
         N: Into<String> + FmtDebug,
+    {
+        self.get_opt(dataset_name, RequestOptions::default()).await
+    }
+
+    /// Like [`Client::get`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn get_opt<N>(
+        &self,
+        dataset_name: N,
+        request_options: RequestOptions,
+    ) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.http_client
+            .get(
+                format!("/v1/datasets/{}", dataset_name.into()),
+                request_options,
+            )
+            .await?
+            .json()
+            .await
+    }
+
+    /// List all fields of the dataset identified by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self))]
+    pub async fn fields<N>(&self, dataset_name: N) -> Result<Vec<DatasetField>>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.fields_opt(dataset_name, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::fields`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn fields_opt<N>(
+        &self,
+        dataset_name: N,
+        request_options: RequestOptions,
+    ) -> Result<Vec<DatasetField>>
+    where
+        N: Into<String> + FmtDebug,
+    {
+        self.http_client
+            .get(
+                format!("/v2/datasets/{}/fields", dataset_name.into()),
+                request_options,
+            )
+            .await?
+            .json()
+            .await
+    }
+
+    /// Get a field of the dataset identified by its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self))]
+    pub async fn field<N, F>(&self, dataset_name: N, field_name: F) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.field_opt(dataset_name, field_name, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::field`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn field_opt<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        request_options: RequestOptions,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.http_client
+            .get(
+                format!(
+                    "/v2/datasets/{}/fields/{}",
+                    dataset_name.into(),
+                    field_name.into()
+                ),
+                request_options,
+            )
+            .await?
+            .json()
+            .await
+    }
+
+    /// Update the metadata of a field of the dataset identified by its id.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::{datasets::FieldUpdate, Client};
+    ///
+    /// # async fn run() -> Result<(), axiom_rs::Error> {
+    /// let client = Client::new()?;
+    /// let field = client.datasets().field("my-dataset", "duration").await?;
+    /// client
+    ///     .datasets()
+    ///     .update_field(
+    ///         "my-dataset",
+    ///         "duration",
+    ///         FieldUpdate {
+    ///             unit: "ms".to_string(),
+    ///             ..field.into()
+    ///         },
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self))]
+    pub async fn update_field<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        update: FieldUpdate,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
+    {
+        self.update_field_opt(dataset_name, field_name, update, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::update_field`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn update_field_opt<N, F>(
+        &self,
+        dataset_name: N,
+        field_name: F,
+        update: FieldUpdate,
+        request_options: RequestOptions,
+    ) -> Result<DatasetField>
+    where
+        N: Into<String> + FmtDebug,
+        F: Into<String> + FmtDebug,
     {
         self.http_client
-            .get(format!("/v1/datasets/{}", dataset_name.into()))
+            .put(
+                format!(
+                    "/v2/datasets/{}/fields/{}",
+                    dataset_name.into(),
+                    field_name.into()
+                ),
+                update,
+                request_options,
+            )
             .await?
             .json()
             .await
@@ -93,7 +316,10 @@ impl<'client> Client<'client> {
         N: Into<String> + FmtDebug,
     {
         self.http_client
-            .get(format!("/v1/datasets/{}/info", dataset_name.into()))
+            .get(
+                format!("/v1/datasets/{}/info", dataset_name.into()),
+                RequestOptions::default(),
+            )
             .await?
             .json()
             .await
@@ -106,7 +332,88 @@ impl<'client> Client<'client> {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self))]
     pub async fn list(&self) -> Result<Vec<Dataset>> {
-        self.http_client.get("/v1/datasets").await?.json().await
+        self.list_opt(RequestOptions::default()).await
+    }
+
+    /// Like [`Client::list`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn list_opt(&self, request_options: RequestOptions) -> Result<Vec<Dataset>> {
+        self.http_client
+            .get("/v1/datasets", request_options)
+            .await?
+            .json()
+            .await
+    }
+
+    /// Trim the dataset identified by its id to a given length. The max
+    /// duration given will mark the oldest timestamp an event can have.
+    /// Older ones will be deleted from the dataset.
+    ///
+    /// The duration can either be a [`std::time::Duration`] or a
+    /// [`chrono::Duration`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::Client;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() -> Result<(), axiom_rs::Error> {
+    /// let client = Client::new()?;
+    /// // Delete everything older than 30 days.
+    /// client
+    ///     .datasets()
+    ///     .trim("my-dataset", Duration::from_secs(30 * 24 * 60 * 60))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the duration is out of range or the HTTP request
+    /// or JSON deserializing fails.
+    #[instrument(skip(self))]
+    pub async fn trim<N, D>(&self, dataset_name: N, max_duration: D) -> Result<TrimResult>
+    where
+        N: Into<String> + FmtDebug,
+        D: TryInto<Duration, Error = Error> + FmtDebug,
+    {
+        self.trim_opt(dataset_name, max_duration, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::trim`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the duration is out of range or the HTTP request
+    /// or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn trim_opt<N, D>(
+        &self,
+        dataset_name: N,
+        max_duration: D,
+        request_options: RequestOptions,
+    ) -> Result<TrimResult>
+    where
+        N: Into<String> + FmtDebug,
+        D: TryInto<Duration, Error = Error> + FmtDebug,
+    {
+        let max_duration: Duration = max_duration.try_into()?;
+        let req = TrimRequest::new(max_duration.into());
+        self.http_client
+            .post(
+                format!("/v1/datasets/{}/trim", dataset_name.into()),
+                &req,
+                request_options,
+            )
+            .await?
+            .json()
+            .await
     }
 
     /// Update a dataset.
@@ -116,6 +423,26 @@ impl<'client> Client<'client> {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self))]
     pub async fn update<N, D>(&self, dataset_name: N, new_description: D) -> Result<Dataset>
+    where
+        N: Into<String> + FmtDebug,
+        D: Into<String> + FmtDebug,
+    {
+        self.update_opt(dataset_name, new_description, RequestOptions::default())
+            .await
+    }
+
+    /// Like [`Client::update`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn update_opt<N, D>(
+        &self,
+        dataset_name: N,
+        new_description: D,
+        request_options: RequestOptions,
+    ) -> Result<Dataset>
     where
         N: Into<String> + FmtDebug,
         D: Into<String> + FmtDebug,
@@ -126,6 +453,7 @@ impl<'client> Client<'client> {
                 DatasetUpdateRequest {
                     description: new_description.into(),
                 },
+                request_options,
             )
             .await?
             .json()
@@ -133,6 +461,8 @@ impl<'client> Client<'client> {
     }
 }
 
+/// A duration accepted by [`Client::trim`], converted from either a
+/// [`std::time::Duration`] or a [`chrono::Duration`].
 pub struct Duration {
     inner: chrono::Duration,
 }
@@ -12,6 +12,8 @@ use std::{
     fmt::{self, Display},
     ops::Add,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 
 pub use table::*;
@@ -122,6 +124,39 @@ impl From<ContentEncoding> for HeaderValue {
     }
 }
 
+/// The compression applied to events before they are ingested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Send the events uncompressed.
+    Identity,
+    /// Compress the events using gzip with the given level (0-9).
+    Gzip(u32),
+    /// Compress the events using zstd with the given level (1-22, 0 selects
+    /// zstd's default level). Only available with the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Compression {
+    /// Returns the content encoding of data compressed this way.
+    #[must_use]
+    pub fn content_encoding(&self) -> ContentEncoding {
+        match self {
+            Compression::Identity => ContentEncoding::Identity,
+            Compression::Gzip(_) => ContentEncoding::Gzip,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => ContentEncoding::Zstd,
+        }
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::Gzip(6)
+    }
+}
+
 /// An Axiom dataset.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Dataset {
@@ -172,6 +207,52 @@ pub struct Info {
     pub fields: Vec<Field>,
 }
 
+/// A field of a dataset and its metadata.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetField {
+    /// The name of the field.
+    pub name: String,
+    /// The description of the field.
+    #[serde(default)]
+    pub description: String,
+    /// The datatype of the field, e.g. `string` or `integer`.
+    #[serde(rename = "type", default)]
+    pub typ: String,
+    /// The unit of the field's values, e.g. `ms` or `By`.
+    #[serde(default)]
+    pub unit: String,
+    /// Whether the field is hidden.
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// The metadata of a field to update, see
+/// [`datasets::Client::update_field`](crate::datasets::Client::update_field).
+///
+/// All values replace the current ones, so start from the current field to
+/// only change some of them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldUpdate {
+    /// The description of the field.
+    pub description: String,
+    /// The unit of the field's values, e.g. `ms` or `By`.
+    pub unit: String,
+    /// Whether the field is hidden.
+    pub hidden: bool,
+}
+
+impl From<DatasetField> for FieldUpdate {
+    fn from(field: DatasetField) -> Self {
+        Self {
+            description: field.description,
+            unit: field.unit,
+            hidden: field.hidden,
+        }
+    }
+}
+
 /// Returned on event ingestion operation.
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
@@ -244,6 +325,31 @@ pub(crate) struct DatasetUpdateRequest {
     pub description: String,
 }
 
+/// Used to trim a dataset.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrimRequest {
+    /// Events older than this duration are deleted, e.g. `720h`.
+    max_duration: String,
+}
+
+impl TrimRequest {
+    pub(crate) fn new(max_duration: chrono::Duration) -> Self {
+        Self {
+            max_duration: format!("{}s", max_duration.num_seconds()),
+        }
+    }
+}
+
+/// The result of a trim operation.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrimResult {
+    /// The amount of blocks deleted by the trim operation.
+    #[serde(default)]
+    pub blocks_deleted: u64,
+}
+
 /// A query that gets executed on a dataset.
 /// If you're looking for the analytics, check out [`Query`].
 #[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
@@ -263,6 +369,31 @@ pub struct Query {
     pub include_cursor_field: bool,
 }
 
+/// The optional parameters to [`Client::query_stream`](crate::Client::query_stream).
+#[derive(Debug, Clone)]
+pub struct QueryStreamOptions {
+    /// The options of every query. `cursor` and `include_cursor` only apply
+    /// to the first page, `include_cursor_field` is always set.
+    pub query_options: QueryOptions,
+    /// The maximum number of rows per page. Defaults to 1000.
+    pub page_size: usize,
+    /// Stop after this many pages, even if there are more results.
+    pub max_pages: Option<usize>,
+    /// The request options of every query.
+    pub request_options: crate::RequestOptions,
+}
+
+impl Default for QueryStreamOptions {
+    fn default() -> Self {
+        Self {
+            query_options: QueryOptions::default(),
+            page_size: 1000,
+            max_pages: None,
+            request_options: crate::RequestOptions::default(),
+        }
+    }
+}
+
 impl Query {
     /// Creates a new query with the given APL and options.
     pub fn new<S: ToString + ?Sized>(apl: &S, opts: QueryOptions) -> Self {
@@ -308,6 +439,125 @@ impl From<&QueryOptions> for QueryParams {
     }
 }
 
+/// The optional parameters to ingest methods.
+#[derive(Debug, Default, Clone)]
+pub struct IngestOptions {
+    /// The compression to use. Defaults to the compression configured on the
+    /// client.
+    pub compression: Option<Compression>,
+    /// Options for every request made while ingesting, e.g. additional
+    /// headers or a cancellation token.
+    pub request_options: crate::RequestOptions,
+}
+
+/// The optional parameters to stream ingest methods like
+/// [`Client::ingest_stream_opt`](crate::Client::ingest_stream_opt).
+///
+/// Events are ingested in chunks, a chunk is sent as soon as one of the
+/// limits is reached.
+#[derive(Debug, Clone)]
+pub struct IngestStreamOptions {
+    /// The options of every ingest request.
+    pub ingest_options: IngestOptions,
+    /// Ingest a chunk once it holds this many events. Values below 1 are
+    /// treated as 1. Defaults to 1000.
+    pub max_events: usize,
+    /// Ingest a chunk once its uncompressed NDJSON payload reaches this many
+    /// bytes. An event that doesn't fit into the current chunk starts the
+    /// next one, so only a single event larger than this exceeds the limit.
+    /// Values below 1 are treated as 1. Defaults to 1 MiB.
+    pub max_bytes: usize,
+    /// Ingest a chunk once its first event has been waiting for this long.
+    /// Defaults to one second.
+    pub max_wait: Duration,
+    /// The number of chunks that are ingested at the same time. Once reached,
+    /// the stream isn't read until one of them is done. Values below 1 are
+    /// treated as 1. Defaults to 1.
+    pub concurrency: usize,
+    /// What to do when a chunk fails to ingest. Defaults to
+    /// [`OnChunkFailure::Abort`].
+    pub on_chunk_failure: OnChunkFailure,
+}
+
+impl Default for IngestStreamOptions {
+    fn default() -> Self {
+        Self {
+            ingest_options: IngestOptions::default(),
+            max_events: 1000,
+            max_bytes: 1024 * 1024,
+            max_wait: Duration::from_secs(1),
+            concurrency: 1,
+            on_chunk_failure: OnChunkFailure::Abort,
+        }
+    }
+}
+
+impl From<IngestOptions> for IngestStreamOptions {
+    fn from(ingest_options: IngestOptions) -> Self {
+        Self {
+            ingest_options,
+            ..Self::default()
+        }
+    }
+}
+
+/// What stream ingest methods do when a chunk fails to ingest, see
+/// [`IngestStreamOptions::on_chunk_failure`].
+///
+/// This only applies to chunks that were rejected or couldn't be sent after
+/// all retries. Errors of the stream itself and events that can't be
+/// serialized always stop the ingestion.
+#[derive(Clone)]
+pub enum OnChunkFailure {
+    /// Stop and return the error. Chunks that are still in flight are
+    /// cancelled.
+    Abort,
+    /// Keep going and collect the failed chunks. If any chunk failed, the
+    /// ingestion returns [`Error::PartialIngest`](crate::Error::PartialIngest)
+    /// with the status of all successful chunks and the failed ones.
+    Collect,
+    /// Keep going and pass every failed chunk to the callback. The ingestion
+    /// returns the status of all successful chunks.
+    Callback(Arc<dyn Fn(FailedChunk) + Send + Sync>),
+}
+
+impl Default for OnChunkFailure {
+    fn default() -> Self {
+        Self::Abort
+    }
+}
+
+impl fmt::Debug for OnChunkFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abort => f.write_str("Abort"),
+            Self::Collect => f.write_str("Collect"),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+/// A chunk of a stream that failed to ingest.
+#[derive(Debug)]
+pub struct FailedChunk {
+    /// The events of the chunk, each serialized to JSON exactly as it was
+    /// sent.
+    pub events: Vec<Vec<u8>>,
+    /// The error the chunk failed with.
+    pub error: crate::Error,
+}
+
+impl FailedChunk {
+    /// Returns the events as the uncompressed NDJSON payload that was sent,
+    /// e.g. to ingest them again with
+    /// [`Client::ingest_bytes`](crate::Client::ingest_bytes) using
+    /// [`ContentType::NdJson`] and [`ContentEncoding::Identity`].
+    #[must_use]
+    pub fn ndjson(&self) -> Vec<u8> {
+        self.events.join(&b"\n"[..])
+    }
+}
+
 // This is a configuration that just happens to have many flags.
 #[allow(clippy::struct_excessive_bools)]
 /// The optional parameters to APL query methods.
@@ -331,6 +581,31 @@ pub struct QueryOptions {
     pub format: AplResultFormat,
     /// Requests the cursor to be included in the response
     pub include_cursor_field: bool,
+    /// The timeout of the query. Defaults to the query timeout configured on
+    /// the client.
+    pub timeout: Option<std::time::Duration>,
+    /// Fail the query with [`Error::QueryMessages`](crate::Error::QueryMessages)
+    /// if the result has messages of this priority or higher, e.g.
+    /// [`QueryMessagePriority::Warn`] to not silently miss a
+    /// [`QueryMessageCode::MissingColumn`]. Disabled by default.
+    pub strict: Option<QueryMessagePriority>,
+}
+
+impl QueryOptions {
+    /// Sets the timeout of the query.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails the query if the result has messages of the given priority or
+    /// higher, see [`QueryOptions::strict`].
+    #[must_use]
+    pub fn with_strict(mut self, priority: QueryMessagePriority) -> Self {
+        self.strict = Some(priority);
+        self
+    }
 }
 
 /// The result format of an APL query.
@@ -533,6 +808,33 @@ pub struct QueryResult {
     pub trace_id: Option<String>,
 }
 
+impl QueryResult {
+    /// Returns the messages of the query with [`QueryMessagePriority::Warn`].
+    pub fn warnings(&self) -> impl Iterator<Item = &QueryMessage> {
+        self.status
+            .messages
+            .iter()
+            .filter(|message| message.priority == QueryMessagePriority::Warn)
+    }
+
+    /// Returns the messages of the query with [`QueryMessagePriority::Error`]
+    /// or higher.
+    pub fn errors(&self) -> impl Iterator<Item = &QueryMessage> {
+        self.messages_at_least(QueryMessagePriority::Error)
+    }
+
+    /// Returns the messages of the query with the given priority or higher.
+    pub fn messages_at_least(
+        &self,
+        priority: QueryMessagePriority,
+    ) -> impl Iterator<Item = &QueryMessage> {
+        self.status
+            .messages
+            .iter()
+            .filter(move |message| message.priority >= priority)
+    }
+}
+
 /// The status of a query result.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -587,7 +889,7 @@ bitflags! {
 impl_serde_for_bitflags!(CacheStatus);
 
 /// A message that is returned in the status of a query.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct QueryMessage {
     priority: QueryMessagePriority,
     count: u32,
@@ -595,8 +897,47 @@ pub struct QueryMessage {
     text: Option<String>,
 }
 
-/// The priority of a query message.
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Copy)]
+impl QueryMessage {
+    /// The priority of the message.
+    #[must_use]
+    pub fn priority(&self) -> QueryMessagePriority {
+        self.priority
+    }
+
+    /// How often the message occurred during the query.
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The code of the message.
+    #[must_use]
+    pub fn code(&self) -> QueryMessageCode {
+        self.code
+    }
+
+    /// The human-readable text of the message, if any.
+    #[must_use]
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+}
+
+impl fmt::Display for QueryMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {:?}", self.priority, self.code)?;
+        if let Some(text) = &self.text {
+            write!(f, ": {text}")?;
+        }
+        if self.count > 1 {
+            write!(f, " ({} times)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The priority of a query message, ordered from lowest to highest.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
 #[non_exhaustive]
 #[serde(rename_all = "camelCase")]
 pub enum QueryMessagePriority {
@@ -1,9 +1,22 @@
-use std::fmt::{self, Display};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+    io::Write,
+};
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{value::MapDeserializer, DeserializeOwned},
+    ser::SerializeMap,
+    Deserialize, Serialize, Serializer,
+};
 use serde_json::value::Value as JsonValue;
 
+use crate::error::{Error, Result};
+
+#[cfg(feature = "arrow")]
+mod arrow;
+
 /// Specifies the order a queries result will be in.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct Order {
@@ -26,6 +39,17 @@ impl FieldType {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Returns true if the field only holds numbers, e.g. `integer` or
+    /// `float|integer`.
+    #[must_use]
+    pub fn is_numeric(&self) -> bool {
+        !self.name.is_empty()
+            && self
+                .name
+                .split('|')
+                .all(|typ| matches!(typ, "integer" | "float" | "number"))
+    }
 }
 
 impl Display for FieldType {
@@ -297,6 +321,141 @@ impl Table {
             row: 0,
         }
     }
+
+    /// Deserializes all rows of the table, see [`Row::deserialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the rows can't be deserialized.
+    pub fn deserialize_rows<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        self.iter().map(|row| row.deserialize()).collect()
+    }
+
+    /// Writes the table as CSV with a header of the field names, see
+    /// [`Row::cell`] for how values are written.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::Client;
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new()?;
+    /// let result = client.query("['my-dataset'] | take 10", None).await?;
+    /// for table in &result.tables {
+    ///     table.write_csv(std::io::stdout().lock())?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        let header = self.fields.iter().map(|field| Cow::Borrowed(field.name()));
+        write_csv_record(&mut writer, header)?;
+        for row in self {
+            write_csv_record(&mut writer, row.cells())?;
+        }
+        writer.flush().map_err(Error::Write)
+    }
+
+    /// Writes the table as newline-delimited JSON, one object per row with
+    /// the fields in the order of [`Table::fields`]. Missing values are
+    /// written as `null`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn write_ndjson<W: Write>(&self, mut writer: W) -> Result<()> {
+        for row in self {
+            serde_json::to_writer(&mut writer, &row)?;
+            writer.write_all(b"\n").map_err(Error::Write)?;
+        }
+        writer.flush().map_err(Error::Write)
+    }
+}
+
+/// Writes a single CSV record, quoting values where necessary.
+fn write_csv_record<'a, W, I>(writer: &mut W, values: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = Cow<'a, str>>,
+{
+    for (i, value) in values.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",").map_err(Error::Write)?;
+        }
+        if value.contains(&[',', '"', '\n', '\r'][..]) {
+            write!(writer, "\"{}\"", value.replace('"', "\"\"")).map_err(Error::Write)?;
+        } else {
+            writer.write_all(value.as_bytes()).map_err(Error::Write)?;
+        }
+    }
+    writer.write_all(b"\r\n").map_err(Error::Write)
+}
+
+/// Renders the table as aligned text with a header of the field names.
+/// Numeric fields are aligned to the right, all others to the left.
+///
+/// ```text
+/// _time                 message  status
+/// --------------------  -------  ------
+/// 2024-01-01T00:00:00Z  hello       200
+/// ```
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<Cow<str>>> = self.iter().map(|row| row.cells().collect()).collect();
+        let widths: Vec<usize> = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .fold(field.name().chars().count(), usize::max)
+            })
+            .collect();
+
+        let header = self.fields.iter().map(|field| Cow::Borrowed(field.name()));
+        self.fmt_line(f, &widths, header)?;
+        let separator = widths.iter().map(|width| Cow::Owned("-".repeat(*width)));
+        self.fmt_line(f, &widths, separator)?;
+        for row in rows {
+            self.fmt_line(f, &widths, row)?;
+        }
+        Ok(())
+    }
+}
+
+impl Table {
+    /// Writes a single line of the text rendering, padding every cell to the
+    /// width of its column.
+    fn fmt_line<'a, I>(&self, f: &mut fmt::Formatter<'_>, widths: &[usize], cells: I) -> fmt::Result
+    where
+        I: IntoIterator<Item = Cow<'a, str>>,
+    {
+        let mut line = String::new();
+        for (i, (cell, (width, field))) in cells
+            .into_iter()
+            .zip(widths.iter().zip(&self.fields))
+            .enumerate()
+        {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            let padding = " ".repeat(width - cell.chars().count());
+            if field.typ().is_numeric() {
+                line.push_str(&padding);
+                line.push_str(&cell);
+            } else {
+                line.push_str(&cell);
+                line.push_str(&padding);
+            }
+        }
+        writeln!(f, "{}", line.trim_end())
+    }
 }
 
 impl<'table> IntoIterator for &'table Table {
@@ -376,6 +535,23 @@ impl<'table> Row<'table> {
     pub fn fields(&self) -> &[Field] {
         &self.table.fields
     }
+    /// Returns the value of the given column as text: strings as they are,
+    /// missing and `null` values as an empty string and all other values as
+    /// JSON.
+    #[must_use]
+    pub fn cell(&self, column: usize) -> Cow<'table, str> {
+        match self.table.columns.get(column).and_then(|c| c.get(self.row)) {
+            None | Some(JsonValue::Null) => Cow::Borrowed(""),
+            Some(JsonValue::String(s)) => Cow::Borrowed(s),
+            Some(value) => Cow::Owned(value.to_string()),
+        }
+    }
+
+    /// Returns all values of the row as text, see [`Row::cell`].
+    fn cells(&self) -> impl Iterator<Item = Cow<'table, str>> + '_ {
+        (0..self.table.fields.len()).map(move |column| self.cell(column))
+    }
+
     /// Returns an iterator over the fields of the row.
     #[must_use]
     pub fn iter(&self) -> FieldIter<'table> {
@@ -385,6 +561,65 @@ impl<'table> Row<'table> {
             index: 0,
         }
     }
+
+    /// Deserializes the row into `T`, mapping field names to the fields of
+    /// `T`.
+    ///
+    /// Missing values are deserialized as `null`, so use `Option` for fields
+    /// that aren't set in every row. `_time` can be deserialized into a
+    /// `DateTime<Utc>`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use axiom_rs::Client;
+    /// use chrono::{DateTime, Utc};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Event {
+    ///     #[serde(rename = "_time")]
+    ///     time: DateTime<Utc>,
+    ///     message: Option<String>,
+    /// }
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = Client::new()?;
+    /// let result = client.query("['my-dataset']", None).await?;
+    /// for table in &result.tables {
+    ///     let events: Vec<Event> = table.deserialize_rows()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the row can't be deserialized into `T`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        let entries = self
+            .table
+            .fields
+            .iter()
+            .zip(self.iter())
+            .map(|(field, value)| (field.name(), value.unwrap_or(&JsonValue::Null)));
+        T::deserialize(MapDeserializer::<_, serde_json::Error>::new(entries)).map_err(|source| {
+            Error::DeserializeRow {
+                row: self.row,
+                source,
+            }
+        })
+    }
+}
+
+/// Serializes the row as a map from field names to values.
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.table.fields.len()))?;
+        for (column, field) in self.table.fields.iter().enumerate() {
+            map.serialize_entry(field.name(), self.get(column).unwrap_or(&JsonValue::Null))?;
+        }
+        map.end()
+    }
 }
 
 impl<'table> IntoIterator for &Row<'table> {
@@ -418,3 +653,125 @@ impl<'table> Iterator for FieldIter<'table> {
         Some(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::Table;
+    use crate::Error;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Event {
+        #[serde(rename = "_time")]
+        time: DateTime<Utc>,
+        message: Option<String>,
+        attributes: Attributes,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Attributes {
+        status: u16,
+    }
+
+    fn table() -> Table {
+        serde_json::from_value(json!({
+            "name": "0",
+            "sources": [{ "name": "test" }],
+            "fields": [
+                { "name": "_time", "type": "datetime" },
+                { "name": "message", "type": "string" },
+                { "name": "attributes", "type": "object" },
+                { "name": "count", "type": "integer" },
+            ],
+            "order": [],
+            "groups": [],
+            "columns": [
+                ["2024-01-01T00:00:00Z", "2024-01-01T00:00:01Z"],
+                ["hello", null],
+                [{ "status": 200 }, { "status": 404 }],
+                [1, 10],
+            ],
+        }))
+        .expect("table is valid")
+    }
+
+    #[test]
+    fn test_deserialize_rows() -> Result<(), Error> {
+        let start = Utc
+            .with_ymd_and_hms(2024, 1, 1, 0, 0, 0)
+            .single()
+            .expect("date is valid");
+        let events: Vec<Event> = table().deserialize_rows()?;
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    time: start,
+                    message: Some("hello".to_string()),
+                    attributes: Attributes { status: 200 },
+                },
+                Event {
+                    time: start + Duration::seconds(1),
+                    message: None,
+                    attributes: Attributes { status: 404 },
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_row_error() {
+        #[derive(Deserialize, Debug)]
+        struct WrongType {
+            #[allow(dead_code)]
+            message: u64,
+        }
+
+        let table = table();
+        let row = table.get_row(1).expect("row exists");
+        match row.deserialize::<WrongType>() {
+            Err(Error::DeserializeRow { row, .. }) => assert_eq!(row, 1),
+            res => panic!("Expected row deserialize error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_write_csv() -> Result<(), Error> {
+        let mut csv = Vec::new();
+        table().write_csv(&mut csv)?;
+        assert_eq!(
+            String::from_utf8_lossy(&csv),
+            "_time,message,attributes,count\r\n\
+             2024-01-01T00:00:00Z,hello,\"{\"\"status\"\":200}\",1\r\n\
+             2024-01-01T00:00:01Z,,\"{\"\"status\"\":404}\",10\r\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_ndjson() -> Result<(), Error> {
+        let mut ndjson = Vec::new();
+        table().write_ndjson(&mut ndjson)?;
+        assert_eq!(
+            String::from_utf8_lossy(&ndjson),
+            "{\"_time\":\"2024-01-01T00:00:00Z\",\"message\":\"hello\",\"attributes\":{\"status\":200},\"count\":1}\n\
+             {\"_time\":\"2024-01-01T00:00:01Z\",\"message\":null,\"attributes\":{\"status\":404},\"count\":10}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            table().to_string(),
+            "_time                 message  attributes      count\n\
+             --------------------  -------  --------------  -----\n\
+             2024-01-01T00:00:00Z  hello    {\"status\":200}      1\n\
+             2024-01-01T00:00:01Z           {\"status\":404}     10\n"
+        );
+    }
+}
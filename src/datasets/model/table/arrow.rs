@@ -0,0 +1,233 @@
+//! Conversion of tables into Arrow record batches.
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, DurationNanosecondArray, Float64Array, Int64Array, RecordBatch,
+    RecordBatchOptions, StringArray, TimestampNanosecondArray,
+};
+use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema, TimeUnit};
+use chrono::DateTime;
+use serde_json::value::Value as JsonValue;
+
+use super::{Field, FieldType, Table};
+use crate::error::{Error, Result};
+
+impl Table {
+    /// Converts the table into an Arrow [`RecordBatch`], one column per field.
+    ///
+    /// Columns are mapped by their [`FieldType`]:
+    ///
+    /// | Field type | Arrow type |
+    /// |------------|------------|
+    /// | `string` | `Utf8` |
+    /// | `integer` | `Int64` |
+    /// | `float` or a mix of numeric types | `Float64` |
+    /// | `boolean` | `Boolean` |
+    /// | `datetime` | `Timestamp(Nanosecond, "UTC")` |
+    /// | `timespan` | `Duration(Nanosecond)` |
+    /// | `dynamic` and everything else | `Utf8`, non-string values as JSON |
+    ///
+    /// All columns are nullable, missing values become nulls.
+    ///
+    /// # Errors
+    ///
+    /// If a value doesn't match the type of its field, e.g. a `datetime` that
+    /// isn't RFC 3339 or a `timespan` that isn't a number of nanoseconds.
+    pub fn to_record_batch(&self) -> Result<RecordBatch> {
+        let len = self.len();
+        let mut fields = Vec::with_capacity(self.fields.len());
+        let mut columns = Vec::with_capacity(self.fields.len());
+        for (index, field) in self.fields.iter().enumerate() {
+            let values = self.columns.get(index).map_or(&[][..], Vec::as_slice);
+            let data_type = data_type(field.typ());
+            columns.push(column(field, &data_type, values, len).map_err(Error::Arrow)?);
+            fields.push(ArrowField::new(field.name(), data_type, true));
+        }
+
+        let options = RecordBatchOptions::new().with_row_count(Some(len));
+        RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), columns, &options)
+            .map_err(Error::Arrow)
+    }
+}
+
+/// Returns the Arrow type for a field type.
+fn data_type(typ: &FieldType) -> DataType {
+    match typ.name() {
+        "string" => DataType::Utf8,
+        "integer" => DataType::Int64,
+        "boolean" => DataType::Boolean,
+        "datetime" => DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        "timespan" => DataType::Duration(TimeUnit::Nanosecond),
+        _ if typ.is_numeric() => DataType::Float64,
+        _ => DataType::Utf8,
+    }
+}
+
+/// Builds the column of a field with the given Arrow type.
+fn column(
+    field: &Field,
+    data_type: &DataType,
+    values: &[JsonValue],
+    len: usize,
+) -> std::result::Result<ArrayRef, ArrowError> {
+    let array: ArrayRef = match data_type {
+        DataType::Int64 => Arc::new(Int64Array::from(convert(
+            field,
+            values,
+            len,
+            JsonValue::as_i64,
+        )?)),
+        DataType::Float64 => Arc::new(Float64Array::from(convert(
+            field,
+            values,
+            len,
+            JsonValue::as_f64,
+        )?)),
+        DataType::Boolean => Arc::new(BooleanArray::from(convert(
+            field,
+            values,
+            len,
+            JsonValue::as_bool,
+        )?)),
+        DataType::Timestamp(..) => Arc::new(
+            TimestampNanosecondArray::from(convert(field, values, len, |value| {
+                DateTime::parse_from_rfc3339(value.as_str()?)
+                    .ok()?
+                    .timestamp_nanos_opt()
+            })?)
+            .with_timezone("UTC"),
+        ),
+        DataType::Duration(..) => Arc::new(DurationNanosecondArray::from(convert(
+            field,
+            values,
+            len,
+            JsonValue::as_i64,
+        )?)),
+        _ => Arc::new(StringArray::from(convert(field, values, len, |value| {
+            Some(match value {
+                JsonValue::String(s) => s.clone(),
+                value => value.to_string(),
+            })
+        })?)),
+    };
+    Ok(array)
+}
+
+/// Converts the values of a column, treating missing values and `null` as
+/// nulls.
+fn convert<T, F>(
+    field: &Field,
+    values: &[JsonValue],
+    len: usize,
+    f: F,
+) -> std::result::Result<Vec<Option<T>>, ArrowError>
+where
+    F: Fn(&JsonValue) -> Option<T>,
+{
+    (0..len)
+        .map(|row| match values.get(row) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(value) => f(value).map(Some).ok_or_else(|| {
+                ArrowError::InvalidArgumentError(format!(
+                    "Value {value} in row {row} of field {} is not a valid {}",
+                    field.name(),
+                    field.typ()
+                ))
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use arrow_array::{
+        cast::AsArray,
+        types::{Int64Type, TimestampNanosecondType},
+        Array,
+    };
+    use arrow_schema::{DataType, TimeUnit};
+    use serde_json::json;
+
+    use super::Table;
+    use crate::Error;
+
+    fn table(fields: &serde_json::Value, columns: &serde_json::Value) -> Table {
+        serde_json::from_value(json!({
+            "name": "0",
+            "sources": [{ "name": "test" }],
+            "fields": fields,
+            "order": [],
+            "groups": [],
+            "columns": columns,
+        }))
+        .expect("table is valid")
+    }
+
+    #[test]
+    fn test_to_record_batch() -> Result<(), Error> {
+        let table = table(
+            &json!([
+                { "name": "_time", "type": "datetime" },
+                { "name": "message", "type": "string" },
+                { "name": "count", "type": "integer" },
+                { "name": "ratio", "type": "float|integer" },
+                { "name": "ok", "type": "boolean" },
+                { "name": "duration", "type": "timespan" },
+                { "name": "attributes", "type": "dynamic" },
+            ]),
+            &json!([
+                ["2024-01-01T00:00:00Z", "2024-01-01T00:00:01.5+01:00"],
+                ["hello", null],
+                [1, null],
+                [0.5, 2],
+                [true, false],
+                [1_000_000_000, null],
+                [{ "status": 200 }, "text"],
+            ]),
+        );
+        let batch = table.to_record_batch()?;
+        assert_eq!(batch.num_rows(), 2);
+
+        let schema = batch.schema();
+        let types: Vec<_> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            [
+                &DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                &DataType::Utf8,
+                &DataType::Int64,
+                &DataType::Float64,
+                &DataType::Boolean,
+                &DataType::Duration(TimeUnit::Nanosecond),
+                &DataType::Utf8,
+            ]
+        );
+        assert!(schema.fields().iter().all(|f| f.is_nullable()));
+
+        let time = batch.column(0).as_primitive::<TimestampNanosecondType>();
+        assert_eq!(time.value(0), 1_704_067_200_000_000_000);
+        assert_eq!(time.value(1), 1_704_063_601_500_000_000);
+        let message = batch.column(1).as_string::<i32>();
+        assert_eq!(message.value(0), "hello");
+        assert!(message.is_null(1));
+        let count = batch.column(2).as_primitive::<Int64Type>();
+        assert_eq!(count.value(0), 1);
+        assert!(count.is_null(1));
+        let attributes = batch.column(6).as_string::<i32>();
+        assert_eq!(attributes.value(0), r#"{"status":200}"#);
+        assert_eq!(attributes.value(1), "text");
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_record_batch_invalid_value() {
+        let table = table(
+            &json!([{ "name": "count", "type": "integer" }]),
+            &json!([[1, "two"]]),
+        );
+        match table.to_record_batch() {
+            Err(Error::Arrow(err)) => assert!(err.to_string().contains("row 1"), "{}", err),
+            res => panic!("Expected arrow error, got {:?}", res),
+        }
+    }
+}
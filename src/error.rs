@@ -1,9 +1,14 @@
 //! Error type definitions.
 
+use chrono::Utc;
 use serde::Deserialize;
-use std::fmt;
+use std::{fmt, time::Duration};
 
-use crate::limits::Limits;
+use crate::{
+    datasets::{FailedChunk, IngestStatus, QueryMessage, QueryMessagePriority},
+    limits::Limits,
+    retry::is_retryable_status,
+};
 
 /// A `Result` alias where the `Err` case is `axiom::Error`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -101,6 +106,134 @@ pub enum Error {
     )]
     /// Personal tokens are not supported for edge endpoints.
     PersonalTokenNotSupportedForEdge,
+    #[error("Failed to deserialize row {row}: {source}")]
+    /// Failed to deserialize a row of a query result.
+    DeserializeRow {
+        /// The index of the row in its table.
+        row: usize,
+        /// The underlying error.
+        source: serde_json::Error,
+    },
+    #[cfg(feature = "blocking")]
+    #[error("Failed to set up the runtime of the blocking client: {0}")]
+    /// Failed to set up the runtime of the blocking client.
+    RuntimeSetup(std::io::Error),
+    #[error("{} chunks of the stream failed to ingest", failed.len())]
+    /// Some chunks of a stream failed to ingest, see
+    /// [`OnChunkFailure::Collect`](crate::datasets::OnChunkFailure::Collect).
+    PartialIngest {
+        /// The status of all chunks that were ingested.
+        status: Box<IngestStatus>,
+        /// The chunks that failed to ingest, never empty.
+        failed: Vec<FailedChunk>,
+    },
+    #[error("Batch ingester is full")]
+    /// Batch ingester is full.
+    BatchIngesterFull,
+    #[error("Batch ingester has been shut down")]
+    /// Batch ingester has been shut down.
+    BatchIngesterClosed,
+    #[error(
+        "Query returned messages at or above {priority:?}: {}",
+        messages.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    /// The result of a query in strict mode has messages at or above the
+    /// chosen priority, see [`QueryOptions::strict`](crate::datasets::QueryOptions::strict).
+    QueryMessages {
+        /// The chosen priority.
+        priority: QueryMessagePriority,
+        /// All messages at or above the priority, never empty.
+        messages: Vec<QueryMessage>,
+    },
+    #[error("Failed to write table: {0}")]
+    /// Failed to write a table.
+    Write(std::io::Error),
+    #[cfg(feature = "arrow")]
+    #[error("Failed to convert table to Arrow: {0}")]
+    /// Failed to convert a table to an Arrow record batch.
+    Arrow(arrow_schema::ArrowError),
+    #[error("Request was cancelled")]
+    /// Request was cancelled using a [`crate::CancellationToken`].
+    Cancelled,
+}
+
+impl Error {
+    /// Returns the HTTP status code of the response that caused the error, if
+    /// there was one.
+    #[must_use]
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Axiom(e) => Some(e.status),
+            Error::Http(e) | Error::Deserialize(e) => e.status().map(|status| status.as_u16()),
+            Error::RateLimitExceeded { .. } => Some(429),
+            Error::QueryLimitExceeded(_) | Error::IngestLimitExceeded(_) => Some(430),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the requested resource doesn't exist.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+
+    /// Returns true if the token is missing, invalid or expired.
+    #[must_use]
+    pub fn is_unauthorized(&self) -> bool {
+        self.status() == Some(401)
+    }
+
+    /// Returns true if the token isn't allowed to do what was requested.
+    #[must_use]
+    pub fn is_forbidden(&self) -> bool {
+        self.status() == Some(403)
+    }
+
+    /// Returns true if sending the same request again may succeed, e.g.
+    /// because it timed out, the server had a temporary problem or a limit
+    /// was exceeded. See [`Error::retry_after`] for how long to wait in the
+    /// latter case.
+    ///
+    /// The status codes considered retryable are
+    /// [`RetryPolicy::SERVER_ERROR_STATUS_CODES`](crate::retry::RetryPolicy::SERVER_ERROR_STATUS_CODES),
+    /// which the default policy doesn't retry on its own.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Http(e) => match e.status() {
+                Some(status) => is_retryable_status(status.as_u16()),
+                None => e.is_timeout() || e.is_connect() || e.is_request(),
+            },
+            Error::Axiom(e) => is_retryable_status(e.status),
+            Error::RateLimitExceeded { .. }
+            | Error::QueryLimitExceeded(_)
+            | Error::IngestLimitExceeded(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the trace id of the failed request, include it when
+    /// reporting a problem to Axiom.
+    #[must_use]
+    pub fn trace_id(&self) -> Option<&str> {
+        match self {
+            Error::Axiom(e) => e.trace_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns how long to wait until the exceeded limit resets, if the error
+    /// is caused by one.
+    #[must_use]
+    pub fn retry_after(&self) -> Option<Duration> {
+        let limits = match self {
+            Error::RateLimitExceeded { limits, .. }
+            | Error::QueryLimitExceeded(limits)
+            | Error::IngestLimitExceeded(limits) => limits,
+            _ => return None,
+        };
+        Some((limits.reset - Utc::now()).to_std().unwrap_or_default())
+    }
 }
 
 /// This is the manual implementation. We don't really care if the error is
@@ -134,6 +267,9 @@ pub struct Axiom {
     /// The trace id.
     #[serde(skip)]
     pub trace_id: Option<String>,
+    /// The raw response body if it couldn't be decoded as an error.
+    #[serde(skip)]
+    pub body: Option<Box<str>>,
 }
 
 impl Axiom {
@@ -150,6 +286,7 @@ impl Axiom {
             path,
             message,
             trace_id,
+            body: None,
         }
     }
 }
@@ -1,24 +1,161 @@
-use backoff::{future::retry, ExponentialBackoffBuilder};
+use backoff::future::retry;
 use bytes::Bytes;
+use chrono::Utc;
+use futures::{
+    future::{self, BoxFuture, Either},
+    Future, FutureExt, TryFutureExt,
+};
 use http::header;
 pub use http::HeaderMap;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{env, time::Duration};
+use std::{
+    env, fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
 use url::Url;
 
 use crate::{
     error::{Axiom, Error, Result},
-    limits::Limit,
+    limits::{Limit, RecordedLimits},
+    middleware::{Middleware, Next},
+    retry::RetryPolicy,
+    runtime,
+    stats::{Endpoint, Stats},
+    CancellationToken, RequestOptions,
 };
 
 static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
-/// Client is a wrapper around `reqwest::Client` which provides automatically
-/// prepending the base url.
+/// Sends the HTTP requests of a [`Client`](crate::Client).
+///
+/// It's implemented for [`reqwest::Client`], which is what's used by default.
+/// Implement it yourself to wrap a client or to replace it with a test
+/// double. Requests already carry all headers the Axiom API needs, including
+/// authentication, and failed requests are retried by the caller according
+/// to the client's [`RetryPolicy`].
+///
+/// # Examples
+/// ```no_run
+/// use axiom_rs::{Client, Transport};
+/// use futures::future::BoxFuture;
+///
+/// #[derive(Debug)]
+/// struct LoggingTransport(reqwest::Client);
+///
+/// impl Transport for LoggingTransport {
+///     fn send(
+///         &self,
+///         request: reqwest::Request,
+///     ) -> BoxFuture<'_, Result<reqwest::Response, reqwest::Error>> {
+///         println!("{} {}", request.method(), request.url());
+///         self.0.send(request)
+///     }
+/// }
+///
+/// # fn main() -> Result<(), axiom_rs::Error> {
+/// let client = Client::builder()
+///     .with_transport(LoggingTransport(reqwest::Client::new()))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Transport: fmt::Debug + Send + Sync + 'static {
+    /// Sends a request and returns its response.
+    ///
+    /// Responses with error status codes are not errors here, they are
+    /// handled by the caller.
+    fn send(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, std::result::Result<reqwest::Response, reqwest::Error>>;
+}
+
+impl Transport for reqwest::Client {
+    fn send(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, std::result::Result<reqwest::Response, reqwest::Error>> {
+        Box::pin(self.execute(request))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'_, std::result::Result<reqwest::Response, reqwest::Error>> {
+        (**self).send(request)
+    }
+}
+
+/// The timeouts of requests, by their kind.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timeouts {
+    pub(crate) management: Duration,
+    pub(crate) ingest: Duration,
+    pub(crate) query: Duration,
+}
+
+impl Timeouts {
+    fn for_kind(&self, kind: RequestKind) -> Duration {
+        match kind {
+            RequestKind::Management => self.management,
+            RequestKind::Ingest => self.ingest,
+            RequestKind::Query => self.query,
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            management: Duration::from_secs(10),
+            ingest: Duration::from_secs(60),
+            query: Duration::from_secs(120),
+        }
+    }
+}
+
+/// The configuration shared by all HTTP clients of a [`crate::Client`].
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub(crate) transport: Arc<dyn Transport>,
+    pub(crate) timeouts: Timeouts,
+    pub(crate) retry_policy: RetryPolicy,
+    /// The latest limits, shared between all clients talking to Axiom.
+    pub(crate) limits: Arc<Mutex<RecordedLimits>>,
+    /// Wait for exceeded limits to reset before sending a request.
+    pub(crate) limit_throttling: bool,
+    /// The middleware every attempt of a request passes through, in order.
+    pub(crate) middleware: Vec<Arc<dyn Middleware>>,
+    /// Statistics about all requests, shared between all clients.
+    pub(crate) stats: Arc<Mutex<Stats>>,
+}
+
+/// Client is a wrapper around a [`Transport`] which provides automatically
+/// prepending the base url and adding the default headers.
 #[derive(Debug, Clone)]
 pub(crate) struct Client {
     base_url: Url,
-    inner: reqwest::Client,
+    default_headers: HeaderMap,
+    config: Config,
+}
+
+/// What a request does, which determines the limits and timeout that apply
+/// to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RequestKind {
+    /// Any request that is neither an ingest nor a query, e.g. listing
+    /// datasets.
+    Management,
+    /// An ingest request.
+    Ingest,
+    /// A query request.
+    Query,
 }
 
 #[derive(Clone)]
@@ -28,9 +165,19 @@ pub(crate) enum Body {
     Bytes(Bytes),
 }
 
+/// A failed attempt to execute a request.
+enum Failure {
+    /// The request failed without a response.
+    Transport(reqwest::Error),
+    /// The request got a response that should be retried.
+    Status(reqwest::Response),
+    /// A middleware failed the request.
+    Middleware(Error),
+}
+
 impl Client {
     /// Creates a new client.
-    pub(crate) fn new<U, T, O>(base_url: U, token: T, org_id: O) -> Result<Self>
+    pub(crate) fn new<U, T, O>(base_url: U, token: T, org_id: O, config: Config) -> Result<Self>
     where
         U: AsRef<str>,
         T: Into<String>,
@@ -48,133 +195,300 @@ impl Client {
                 header::HeaderValue::from_str(&org_id).map_err(|_e| Error::InvalidOrgId)?;
             default_headers.insert("X-Axiom-Org-Id", org_id_header_value);
         }
-
-        let http_client = reqwest::Client::builder()
-            .user_agent(USER_AGENT)
-            .default_headers(default_headers)
-            .timeout(Duration::from_secs(10))
-            .build()
-            .map_err(Error::HttpClientSetup)?;
+        default_headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_static(USER_AGENT),
+        );
 
         Ok(Self {
             base_url,
-            inner: http_client,
+            default_headers,
+            config,
         })
     }
 
-    async fn execute<P, H>(
+    async fn execute<P>(
         &self,
+        kind: RequestKind,
         method: http::Method,
         path: P,
         body: Body,
-        headers: H,
+        options: RequestOptions,
     ) -> Result<Response>
     where
         P: AsRef<str>,
-        H: Into<Option<HeaderMap>>,
     {
         let url = self
             .base_url
             .join(path.as_ref().trim_start_matches('/'))
             .map_err(Error::InvalidUrl)?;
+        let endpoint = &Endpoint::new(kind, &method, path.as_ref());
+
+        let mut request_headers = self.default_headers.clone();
+        request_headers.extend(options.additional_headers);
+        let timeout = options
+            .timeout
+            .unwrap_or_else(|| self.config.timeouts.for_kind(kind));
+        let body = match body {
+            Body::Empty => None,
+            Body::Json(value) => {
+                request_headers.insert(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static("application/json"),
+                );
+                Some(Bytes::from(
+                    serde_json::to_vec(&value).map_err(Error::Serialize)?,
+                ))
+            }
+            Body::Bytes(bytes) => Some(bytes),
+        };
+
+        let policy = options
+            .retry_policy
+            .as_ref()
+            .unwrap_or(&self.config.retry_policy);
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+
+        let res = retry(policy.backoff(), || async {
+            let attempt = attempts.fetch_add(1, Ordering::Relaxed) + 1;
+            let is_last_attempt = policy.is_last_attempt(attempt);
+
+            if self.config.limit_throttling {
+                if let Some(wait) = self.recorded_limits().wait_time(kind) {
+                    runtime::sleep(wait).await;
+                }
+            }
+
+            let mut req = reqwest::Request::new(method.clone(), url.clone());
+            *req.headers_mut() = request_headers.clone();
+            *req.body_mut() = body.clone().map(reqwest::Body::from);
+            *req.timeout_mut() = Some(timeout);
 
-        let headers = headers.into();
-        let backoff = ExponentialBackoffBuilder::new()
-            .with_initial_interval(Duration::from_millis(500)) // first retry after 500ms
-            .with_multiplier(2.0) // all following retries are twice as long as the previous one
-            .with_max_elapsed_time(Some(Duration::from_secs(30))) // try up to 30s
-            .build();
-
-        let res = retry(backoff, || async {
-            let mut req = self.inner.request(method.clone(), url.clone());
-            if let Some(headers) = headers.clone() {
-                req = req.headers(headers);
+            let res = match self.send(endpoint, req, attempt).await {
+                Ok(res) => res,
+                Err(Error::Http(e)) if policy.retry_transport_errors && !is_last_attempt => {
+                    return Err(backoff::Error::transient(Failure::Transport(e)));
+                }
+                Err(Error::Http(e)) => {
+                    return Err(backoff::Error::permanent(Failure::Transport(e)))
+                }
+                Err(e) => return Err(backoff::Error::permanent(Failure::Middleware(e))),
+            };
+            self.record_limits(&res);
+            if is_last_attempt {
+                return Ok(res);
             }
-            match body.clone() {
-                Body::Empty => {}
-                Body::Json(value) => req = req.json(&value),
-                Body::Bytes(bytes) => req = req.body(bytes),
+            if policy.is_retryable_status(res.status().as_u16()) {
+                return Err(backoff::Error::transient(Failure::Status(res)));
             }
-            self.inner.execute(req.build()?).await.map_err(|e| {
-                if let Some(status) = e.status() {
-                    if status.is_client_error() {
-                        // Don't retry 4XX
-                        return backoff::Error::permanent(e);
+            if policy.retry_limits {
+                if let Some(limit) = Limit::try_from(&res) {
+                    // The backoff doesn't check the elapsed time when told
+                    // how long to wait, so we have to.
+                    let wait = (limit.limits().reset - Utc::now())
+                        .to_std()
+                        .unwrap_or_default();
+                    let within_max_elapsed_time = policy
+                        .max_elapsed_time
+                        .map_or(true, |max| started.elapsed() + wait <= max);
+                    if within_max_elapsed_time {
+                        return Err(backoff::Error::retry_after(Failure::Status(res), wait));
                     }
                 }
+            }
+            Ok(res)
+        });
 
-                backoff::Error::transient(e)
-            })
-        })
-        .await
-        .map(|res| Response::new(res, method, path.as_ref().to_string()))
-        .map_err(Error::Http)?;
+        let res = match cancellable(options.cancellation_token.as_ref(), res.map(Ok)).await? {
+            Ok(res) | Err(Failure::Status(res)) => res,
+            Err(Failure::Transport(e)) => return Err(Error::Http(e)),
+            Err(Failure::Middleware(e)) => return Err(e),
+        };
+        let res = Response::new(
+            res,
+            method,
+            path.as_ref().to_string(),
+            options.cancellation_token,
+        );
 
         Ok(res)
     }
 
-    pub(crate) async fn get<S>(&self, path: S) -> Result<Response>
+    /// Sends a single attempt of a request through all middleware and
+    /// records it in the statistics.
+    async fn send(
+        &self,
+        endpoint: &Endpoint,
+        req: reqwest::Request,
+        attempt: u32,
+    ) -> Result<reqwest::Response> {
+        let body_len = req
+            .body()
+            .and_then(reqwest::Body::as_bytes)
+            .map_or(0, <[u8]>::len);
+        let next = Next::new(
+            &self.config.middleware,
+            self.config.transport.as_ref(),
+            attempt,
+        );
+        let sent = Instant::now();
+        let res = next.run(req).await;
+        self.update_stats(|stats| {
+            let status = res.as_ref().ok().map(|res| res.status().as_u16());
+            stats.record_request(endpoint, status, sent.elapsed());
+            stats.bytes_sent += body_len as u64;
+            if attempt > 1 {
+                stats.retries += 1;
+            }
+        });
+        res
+    }
+
+    /// Returns a copy of the latest limits.
+    pub(crate) fn recorded_limits(&self) -> RecordedLimits {
+        self.config
+            .limits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Returns a copy of the current statistics.
+    pub(crate) fn stats(&self) -> Stats {
+        self.config
+            .stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Updates the statistics shared by all clients.
+    pub(crate) fn update_stats<F: FnOnce(&mut Stats)>(&self, f: F) {
+        f(&mut self
+            .config
+            .stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner));
+    }
+
+    fn record_limits(&self, res: &reqwest::Response) {
+        let mut limits = self
+            .config
+            .limits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for limit in Limit::all_from(res.headers()) {
+            limits.record(limit);
+        }
+    }
+
+    pub(crate) async fn get<S>(&self, path: S, options: RequestOptions) -> Result<Response>
     where
         S: AsRef<str>,
     {
-        self.execute(http::Method::GET, path.as_ref(), Body::Empty, None)
-            .await
+        self.execute(
+            RequestKind::Management,
+            http::Method::GET,
+            path.as_ref(),
+            Body::Empty,
+            options,
+        )
+        .await
+    }
+
+    pub(crate) async fn post<S, P>(
+        &self,
+        path: S,
+        payload: P,
+        options: RequestOptions,
+    ) -> Result<Response>
+    where
+        S: AsRef<str>,
+        P: Serialize,
+    {
+        self.execute(
+            RequestKind::Management,
+            http::Method::POST,
+            path,
+            Body::Json(serde_json::to_value(payload).map_err(Error::Serialize)?),
+            options,
+        )
+        .await
     }
 
-    pub(crate) async fn post<S, P>(&self, path: S, payload: P) -> Result<Response>
+    pub(crate) async fn post_query<S, P>(
+        &self,
+        path: S,
+        payload: P,
+        options: RequestOptions,
+    ) -> Result<Response>
     where
         S: AsRef<str>,
         P: Serialize,
     {
         self.execute(
+            RequestKind::Query,
             http::Method::POST,
             path,
             Body::Json(serde_json::to_value(payload).map_err(Error::Serialize)?),
-            None,
+            options,
         )
         .await
     }
 
-    pub(crate) async fn post_bytes<S, P, H>(
+    pub(crate) async fn post_bytes<S, P>(
         &self,
         path: S,
         payload: P,
-        headers: H,
+        options: RequestOptions,
     ) -> Result<Response>
     where
         S: AsRef<str>,
         P: Into<Bytes>,
-        H: Into<Option<HeaderMap>>,
     {
         self.execute(
+            RequestKind::Ingest,
             http::Method::POST,
             path,
             Body::Bytes(payload.into()),
-            headers,
+            options,
         )
         .await
     }
 
-    pub(crate) async fn put<S, P>(&self, path: S, payload: P) -> Result<Response>
+    pub(crate) async fn put<S, P>(
+        &self,
+        path: S,
+        payload: P,
+        options: RequestOptions,
+    ) -> Result<Response>
     where
         S: AsRef<str>,
         P: Serialize,
     {
         self.execute(
+            RequestKind::Management,
             http::Method::PUT,
             path,
             Body::Json(serde_json::to_value(payload).map_err(Error::Serialize)?),
-            None,
+            options,
         )
         .await
     }
 
-    pub(crate) async fn delete<S>(&self, path: S) -> Result<()>
+    pub(crate) async fn delete<S>(&self, path: S, options: RequestOptions) -> Result<()>
     where
         S: AsRef<str>,
     {
-        self.execute(http::Method::DELETE, path, Body::Empty, None)
-            .await?;
+        self.execute(
+            RequestKind::Management,
+            http::Method::DELETE,
+            path,
+            Body::Empty,
+            options,
+        )
+        .await?;
         Ok(())
     }
 }
@@ -185,26 +499,35 @@ pub(crate) struct Response {
     method: http::Method,
     path: String,
     limits: Option<Limit>,
+    /// Cancels reading the body, too.
+    cancellation_token: Option<CancellationToken>,
 }
 
 impl Response {
-    pub(crate) fn new(inner: reqwest::Response, method: http::Method, path: String) -> Self {
+    pub(crate) fn new(
+        inner: reqwest::Response,
+        method: http::Method,
+        path: String,
+        cancellation_token: Option<CancellationToken>,
+    ) -> Self {
         let limits = Limit::try_from(&inner);
         Self {
             inner,
             method,
             path,
             limits,
+            cancellation_token,
         }
     }
 
     pub(crate) async fn json<T: DeserializeOwned>(self) -> Result<T> {
-        self.check_error()
-            .await?
-            .inner
-            .json::<T>()
-            .await
-            .map_err(Error::Deserialize)
+        let Self {
+            inner,
+            cancellation_token,
+            ..
+        } = self.check_error().await?;
+        let body = inner.json::<T>().map_err(Error::Deserialize);
+        cancellable(cancellation_token.as_ref(), body).await
     }
 
     pub(crate) async fn check_error(self) -> Result<Response> {
@@ -230,26 +553,22 @@ impl Response {
             }
 
             // Try to decode the error
-            let e = match self.inner.json::<Axiom>().await {
-                Ok(mut e) => {
-                    e.status = status.as_u16();
-                    e.method = self.method;
-                    e.path = self.path;
-                    e.trace_id = trace_id;
-                    Error::Axiom(e)
-                }
-                Err(_e) => {
-                    // Decoding failed, we still want an AxiomError
-                    Error::Axiom(Axiom::new(
-                        status.as_u16(),
-                        self.method,
-                        self.path,
-                        None,
-                        trace_id,
-                    ))
-                }
+            let body = self.inner.text().map(|body| Ok(body.ok()));
+            let body = cancellable(self.cancellation_token.as_ref(), body).await?;
+            let e = if let Some(Ok(mut e)) = body.as_deref().map(serde_json::from_str::<Axiom>) {
+                e.status = status.as_u16();
+                e.method = self.method;
+                e.path = self.path;
+                e.trace_id = trace_id;
+                e
+            } else {
+                // Decoding failed, we still want an AxiomError and keep the
+                // body for debugging.
+                let mut e = Axiom::new(status.as_u16(), self.method, self.path, None, trace_id);
+                e.body = body.filter(|body| !body.is_empty()).map(Into::into);
+                e
             };
-            return Err(e);
+            return Err(Error::Axiom(e));
         }
 
         Ok(self)
@@ -260,6 +579,21 @@ impl Response {
     }
 }
 
+/// Runs the future until it completes or the token is cancelled.
+async fn cancellable<F, T>(token: Option<&CancellationToken>, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match token {
+        Some(token) if token.is_cancelled() => Err(Error::Cancelled),
+        Some(token) => match future::select(Box::pin(future), Box::pin(token.cancelled())).await {
+            Either::Left((res, _)) => res,
+            Either::Right(((), _)) => Err(Error::Cancelled),
+        },
+        None => future.await,
+    }
+}
+
 impl From<Response> for reqwest::Response {
     fn from(res: Response) -> Self {
         res.inner
@@ -269,10 +603,88 @@ impl From<Response> for reqwest::Response {
 #[cfg(test)]
 mod test {
     use chrono::{Duration, Utc};
+    use futures::TryStreamExt;
     use httpmock::prelude::*;
     use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        datasets::{
+            Compression, FailedChunk, FieldUpdate, IngestOptions, IngestStreamOptions,
+            OnChunkFailure, QueryMessageCode, QueryMessagePriority, QueryOptions,
+            QueryStreamOptions, Table,
+        },
+        limits,
+        middleware::{Middleware, Next},
+        retry::RetryPolicy,
+        stats::{Endpoint, RequestKind},
+        trace_context::TraceContext,
+        CancellationToken, Client, Error, HeaderMap, RequestOptions, Transport,
+    };
+    use futures::future::BoxFuture;
+
+    const INGEST_EVENTS: &str = "{\"foo\":\"bar\"}\n{\"foo\":\"baz\"}";
+
+    #[cfg(feature = "zstd")]
+    fn zstd_body_round_trips(req: &HttpMockRequest) -> bool {
+        let body = req.body.as_deref().unwrap_or_default();
+        zstd::decode_all(body).map_or(false, |body| body == INGEST_EVENTS.as_bytes())
+    }
+
+    fn identity_body_round_trips(req: &HttpMockRequest) -> bool {
+        req.body.as_deref() == Some(INGEST_EVENTS.as_bytes())
+    }
+
+    fn query_cursor(req: &HttpMockRequest) -> Option<String> {
+        let body = req.body.as_deref().unwrap_or_default();
+        serde_json::from_slice::<serde_json::Value>(body)
+            .ok()?
+            .get("cursor")?
+            .as_str()
+            .map(ToString::to_string)
+    }
+
+    fn is_first_page(req: &HttpMockRequest) -> bool {
+        query_cursor(req).is_none()
+    }
+
+    fn is_second_page(req: &HttpMockRequest) -> bool {
+        query_cursor(req).as_deref() == Some("c2")
+    }
+
+    fn query_page(cursors: &[&str]) -> serde_json::Value {
+        json!({
+            "status": {
+                "elapsedTime": 1000,
+                "blocksExamined": 1,
+                "rowsExamined": cursors.len(),
+                "rowsMatched": cursors.len(),
+                "numGroups": 0,
+                "isPartial": false,
+                "cacheStatus": 0,
+                "minBlockTime": "2021-01-01T00:00:00Z",
+                "maxBlockTime": "2021-01-01T00:00:00Z"
+            },
+            "tables": [{
+                "name": "0",
+                "sources": [{ "name": "test" }],
+                "fields": [{ "name": "_cursor", "type": "string" }],
+                "order": [],
+                "groups": [],
+                "columns": [cursors]
+            }]
+        })
+    }
 
-    use crate::{limits, Client, Error};
+    fn fast_retry_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_interval: std::time::Duration::from_millis(1),
+            max_attempts: Some(max_attempts),
+            retry_status_codes: RetryPolicy::SERVER_ERROR_STATUS_CODES.to_vec(),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
 
     #[tokio::test]
     async fn test_ingest_limit_exceeded() -> Result<(), Box<dyn std::error::Error>> {
@@ -436,7 +848,7 @@ mod test {
             .with_token("xaat-test")
             .with_edge("eu-central-1.aws.edge.axiom.co")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         assert!(client.uses_edge());
         assert_eq!(client.edge_url(), "https://eu-central-1.aws.edge.axiom.co");
@@ -554,7 +966,7 @@ mod test {
             .with_token("xaat-test")
             .with_edge("eu-central-1.aws.edge.axiom.co")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         assert_eq!(client.edge_url(), "https://eu-central-1.aws.edge.axiom.co");
         assert!(client.uses_edge());
@@ -568,7 +980,7 @@ mod test {
             .with_edge("eu-central-1.aws.edge.axiom.co")
             .with_edge_url("https://custom.ingest.endpoint")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         assert_eq!(client.edge_url(), "https://custom.ingest.endpoint");
     }
@@ -580,7 +992,7 @@ mod test {
             .no_env()
             .with_token("xaat-test")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         assert_eq!(client.api_url(), "https://api.axiom.co");
         assert_eq!(client.edge_url(), "https://api.axiom.co");
@@ -595,7 +1007,7 @@ mod test {
             .with_token("xaat-test")
             .with_url("https://my-axiom-instance.example.com")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         assert_eq!(client.api_url(), "https://my-axiom-instance.example.com");
         assert_eq!(client.edge_url(), "https://my-axiom-instance.example.com");
@@ -613,7 +1025,7 @@ mod test {
             .with_token("xapt-personal-token")
             .with_edge("eu-central-1.aws.edge.axiom.co")
             .build()
-            .unwrap();
+            .expect("client config is valid");
 
         let result = client
             .ingest("test-dataset", vec![serde_json::json!({"foo": "bar"})])
@@ -665,4 +1077,1342 @@ mod test {
         query_mock.assert_hits_async(1).await;
         Ok(())
     }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn test_ingest_zstd() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .header("Content-Encoding", "zstd")
+                .header("Content-Type", "application/x-ndjson")
+                .matches(zstd_body_round_trips);
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_compression(Compression::Zstd(3))
+            .build()?;
+
+        let events = vec![json!({"foo": "bar"}), json!({"foo": "baz"})];
+        client.ingest("test", events.clone()).await?;
+        client
+            .ingest_stream("test", futures::stream::iter(events))
+            .await?;
+
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ingest_identity_per_call() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .header("Content-Type", "application/x-ndjson")
+                .matches(identity_body_round_trips);
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        // The per-call option overrides the client's compression.
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_compression(Compression::Gzip(6))
+            .build()?;
+
+        let events = vec![json!({"foo": "bar"}), json!({"foo": "baz"})];
+        let opts = IngestOptions {
+            compression: Some(Compression::Identity),
+            ..Default::default()
+        };
+        client
+            .ingest_opt("test", events.clone(), opts.clone())
+            .await?;
+        client
+            .try_ingest_stream_opt(
+                "test",
+                futures::stream::iter(events.into_iter().map(Ok::<_, std::io::Error>)),
+                opts,
+            )
+            .await?;
+
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ingest_stream_chunking() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        // Every event is 8 bytes including the newline.
+        let events: Vec<_> = (0..5).map(|i| json!({ "i": i })).collect();
+
+        let opts = IngestStreamOptions {
+            max_events: 2,
+            ..Default::default()
+        };
+        let status = client
+            .ingest_stream_opt("test", futures::stream::iter(events.clone()), opts)
+            .await?;
+        assert_eq!(status.ingested, 6);
+        mock.assert_hits_async(3).await;
+
+        let opts = IngestStreamOptions {
+            max_bytes: 20,
+            ..Default::default()
+        };
+        client
+            .ingest_stream_opt("test", futures::stream::iter(events.clone()), opts)
+            .await?;
+        mock.assert_hits_async(6).await;
+
+        // The second event arrives after the first chunk expired.
+        let late_event = futures::stream::once(async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            json!({ "i": 1 })
+        });
+        let stream =
+            futures::StreamExt::chain(futures::stream::iter(events[..1].to_vec()), late_event);
+        let opts = IngestStreamOptions {
+            max_wait: std::time::Duration::from_millis(20),
+            ..Default::default()
+        };
+        client.ingest_stream_opt("test", stream, opts).await?;
+        mock.assert_hits_async(8).await;
+
+        // Limits of zero are treated as one instead of dropping the events.
+        let opts = IngestStreamOptions {
+            max_events: 0,
+            ..Default::default()
+        };
+        client
+            .ingest_stream_opt("test", futures::stream::iter(events.clone()), opts)
+            .await?;
+        mock.assert_hits_async(13).await;
+
+        let opts = IngestStreamOptions {
+            max_bytes: 0,
+            ..Default::default()
+        };
+        client
+            .ingest_stream_opt("test", futures::stream::iter(events), opts)
+            .await?;
+        mock.assert_hits_async(18).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ingest_stream_concurrency() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200)
+                .json_body(json!({
+                    "ingested": 1,
+                    "failed": 0,
+                    "failures": [],
+                    "processedBytes": 100,
+                    "blocksCreated": 0,
+                    "walLength": 0
+                }))
+                .delay(std::time::Duration::from_millis(200));
+        });
+
+        let in_flight = Arc::new(InFlightMiddleware::default());
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_middleware(in_flight.clone())
+            .build()?;
+
+        let events: Vec<_> = (0..4).map(|i| json!({ "i": i })).collect();
+        let opts = IngestStreamOptions {
+            max_events: 1,
+            concurrency: 2,
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        let status = client
+            .ingest_stream_opt("test", futures::stream::iter(events), opts)
+            .await?;
+        let elapsed = started.elapsed();
+
+        // Two rounds of two overlapping requests each.
+        assert_eq!(status.ingested, 4);
+        assert_eq!(
+            in_flight
+                .max_in_flight
+                .load(std::sync::atomic::Ordering::SeqCst),
+            2
+        );
+        assert!(
+            elapsed >= std::time::Duration::from_millis(400),
+            "{:?}",
+            elapsed
+        );
+        mock.assert_hits_async(4).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ingest_stream_chunk_failures() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let failing = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .body_contains("fail");
+            then.status(400)
+                .json_body(json!({ "message": "invalid event" }));
+        });
+        let succeeding = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .matches(|req| {
+                    !String::from_utf8_lossy(req.body.as_deref().unwrap_or_default())
+                        .contains("fail")
+                });
+            then.status(200).json_body(json!({
+                "ingested": 1,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_compression(Compression::Identity)
+            .build()?;
+
+        let events = vec![
+            json!({ "i": 0 }),
+            json!({ "fail": true }),
+            json!({ "i": 2 }),
+        ];
+        let opts = |on_chunk_failure| IngestStreamOptions {
+            max_events: 1,
+            on_chunk_failure,
+            ..Default::default()
+        };
+
+        match client
+            .ingest_stream_opt(
+                "test",
+                futures::stream::iter(events.clone()),
+                opts(OnChunkFailure::Abort),
+            )
+            .await
+        {
+            Err(Error::Axiom(e)) => assert_eq!(e.status, 400),
+            res => panic!("Expected axiom error, got {:?}", res),
+        }
+
+        match client
+            .ingest_stream_opt(
+                "test",
+                futures::stream::iter(events.clone()),
+                opts(OnChunkFailure::Collect),
+            )
+            .await
+        {
+            Err(Error::PartialIngest { status, failed }) => {
+                assert_eq!(status.ingested, 2);
+                assert_eq!(failed.len(), 1);
+                assert_eq!(failed[0].events, vec![br#"{"fail":true}"#.to_vec()]);
+                assert_eq!(failed[0].ndjson(), br#"{"fail":true}"#);
+                assert_eq!(failed[0].error.status(), Some(400));
+            }
+            res => panic!("Expected partial ingest error, got {:?}", res),
+        }
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let callback = {
+            let reported = reported.clone();
+            OnChunkFailure::Callback(Arc::new(move |chunk: FailedChunk| {
+                reported
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .extend(chunk.events);
+            }))
+        };
+        let status = client
+            .ingest_stream_opt("test", futures::stream::iter(events), opts(callback))
+            .await?;
+        assert_eq!(status.ingested, 2);
+        assert_eq!(
+            *reported.lock().expect("lock is not poisoned"),
+            vec![br#"{"fail":true}"#.to_vec()]
+        );
+
+        failing.assert_hits_async(3).await;
+        succeeding.assert_hits_async(5).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_errors() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(503)
+                .json_body(json!({ "message": "service unavailable" }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(fast_retry_policy(3))
+            .build()?;
+
+        match client.datasets().list().await {
+            Err(Error::Axiom(e)) => assert_eq!(e.status, 503),
+            res => panic!("Expected axiom error, got {:?}", res),
+        }
+
+        mock.assert_hits_async(3).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_status_retries_by_default() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(502).body("Bad Gateway");
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        // The events may have been ingested before the gateway failed.
+        let events = vec![json!({"foo": "bar"})];
+        match client.ingest("test", events).await {
+            Err(Error::Axiom(e)) => assert_eq!(e.status, 502),
+            res => panic!("Expected axiom error, got {:?}", res),
+        }
+
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_disabled() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(503)
+                .json_body(json!({ "message": "service unavailable" }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(RetryPolicy::disabled())
+            .build()?;
+
+        assert!(client.datasets().list().await.is_err());
+
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_limits() -> Result<(), Box<dyn std::error::Error>> {
+        // The limit has already been reset, so there's no need to wait.
+        let reset = Utc::now() - Duration::seconds(1);
+
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(429)
+                .json_body(json!({ "message": "rate limit exceeded" }))
+                .header(limits::HEADER_RATE_SCOPE, "user")
+                .header(limits::HEADER_RATE_LIMIT, "42")
+                .header(limits::HEADER_RATE_REMAINING, "0")
+                .header(limits::HEADER_RATE_RESET, format!("{}", reset.timestamp()));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(RetryPolicy {
+                retry_limits: true,
+                ..fast_retry_policy(2)
+            })
+            .build()?;
+
+        match client.datasets().list().await {
+            Err(Error::RateLimitExceeded { scope, .. }) => assert_eq!(scope, "user"),
+            res => panic!("Expected rate limit error, got {:?}", res),
+        }
+
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limits_recorded_from_successful_responses(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let reset = Utc::now() + Duration::minutes(1);
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200)
+                .json_body(json!({
+                    "ingested": 2,
+                    "failed": 0,
+                    "failures": [],
+                    "processedBytes": 100,
+                    "blocksCreated": 0,
+                    "walLength": 0
+                }))
+                .header(limits::HEADER_INGEST_LIMIT, "42")
+                .header(limits::HEADER_INGEST_REMAINING, "41")
+                .header(
+                    limits::HEADER_INGEST_RESET,
+                    format!("{}", reset.timestamp()),
+                )
+                .header(limits::HEADER_RATE_SCOPE, "organization")
+                .header(limits::HEADER_RATE_LIMIT, "100")
+                .header(limits::HEADER_RATE_REMAINING, "99")
+                .header(limits::HEADER_RATE_RESET, format!("{}", reset.timestamp()));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        assert!(client.limits().ingest.is_none());
+        client.ingest("test", vec![json!({"foo": "bar"})]).await?;
+
+        let recorded = client.limits();
+        let ingest = recorded.ingest.expect("ingest limit is recorded");
+        assert_eq!(ingest.limit, 42);
+        assert_eq!(ingest.remaining, 41);
+        assert_eq!(ingest.reset.timestamp(), reset.timestamp());
+        assert_eq!(recorded.rate_scope.as_deref(), Some("organization"));
+        assert_eq!(recorded.rate.map(|rate| rate.remaining), Some(99));
+        assert!(recorded.query.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limit_throttling() -> Result<(), Box<dyn std::error::Error>> {
+        let reset = Utc::now() + Duration::seconds(2);
+
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(200)
+                .json_body(json!([]))
+                .header(limits::HEADER_RATE_SCOPE, "user")
+                .header(limits::HEADER_RATE_LIMIT, "1")
+                .header(limits::HEADER_RATE_REMAINING, "0")
+                .header(limits::HEADER_RATE_RESET, format!("{}", reset.timestamp()));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_limit_throttling(true)
+            .build()?;
+
+        client.datasets().list().await?;
+        // The rate limit is exhausted, so this waits for it to reset.
+        client.datasets().list().await?;
+        assert!(Utc::now().timestamp() >= reset.timestamp());
+
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let first_page = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/_apl")
+                .body_contains("| limit 2")
+                .matches(is_first_page);
+            then.status(200).json_body(query_page(&["c1", "c2"]));
+        });
+        let second_page = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/_apl")
+                .json_body_partial(r#"{ "includeCursor": false }"#)
+                .matches(is_second_page);
+            then.status(200).json_body(query_page(&["c3"]));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let pages: Vec<_> = client
+            .query_stream(
+                "test | sort by _time desc",
+                QueryStreamOptions {
+                    page_size: 2,
+                    ..Default::default()
+                },
+            )
+            .try_collect()
+            .await?;
+        let lens: Vec<_> = pages.iter().map(Table::len).collect();
+        assert_eq!(lens, vec![2, 1]);
+
+        first_page.assert_hits_async(1).await;
+        second_page.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_stream_stuck_cursor() -> Result<(), Box<dyn std::error::Error>> {
+        // The server ignores the cursor and always returns the same page.
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/_apl");
+            then.status(200).json_body(query_page(&["c1", "c2"]));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let pages: Vec<_> = client
+            .query_stream(
+                "test | sort by _time desc",
+                QueryStreamOptions {
+                    page_size: 2,
+                    ..Default::default()
+                },
+            )
+            .try_collect()
+            .await?;
+        assert_eq!(pages.len(), 1);
+
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trim() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/trim")
+                .json_body(json!({ "maxDuration": "3600s" }));
+            then.status(200).json_body(json!({}));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        client.datasets().trim("test", Duration::hours(1)).await?;
+
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fields() -> Result<(), Box<dyn std::error::Error>> {
+        let field = json!({
+            "name": "duration",
+            "description": "Request duration",
+            "type": "float",
+            "unit": "ms",
+            "hidden": false
+        });
+
+        let server = MockServer::start();
+        let list_mock = server.mock(|when, then| {
+            when.method(GET).path("/v2/datasets/test/fields");
+            then.status(200).json_body(json!([field]));
+        });
+        let update_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/v2/datasets/test/fields/duration")
+                .json_body(json!({
+                    "description": "Request duration",
+                    "unit": "ms",
+                    "hidden": false
+                }));
+            then.status(200).json_body(field.clone());
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let fields = client.datasets().fields("test").await?;
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].typ, "float");
+
+        let update = FieldUpdate {
+            description: "Request duration".to_string(),
+            unit: "ms".to_string(),
+            hidden: false,
+        };
+        let updated = client
+            .datasets()
+            .update_field("test", "duration", update)
+            .await?;
+        assert_eq!(updated, fields[0]);
+
+        list_mock.assert_hits_async(1).await;
+        update_mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    /// Answers every request with an empty JSON array and records it.
+    #[derive(Debug, Default)]
+    struct RecordingTransport {
+        requests: std::sync::Mutex<Vec<(http::Method, String, Option<String>)>>,
+    }
+
+    impl Transport for RecordingTransport {
+        fn send(
+            &self,
+            request: reqwest::Request,
+        ) -> BoxFuture<'_, std::result::Result<reqwest::Response, reqwest::Error>> {
+            let authorization = request
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string);
+            self.requests.lock().expect("lock is not poisoned").push((
+                request.method().clone(),
+                request.url().path().to_string(),
+                authorization,
+            ));
+            let response = http::Response::builder()
+                .status(200)
+                .body("[]")
+                .expect("response is valid");
+            Box::pin(async move { Ok(reqwest::Response::from(response)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_transport() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = std::sync::Arc::new(RecordingTransport::default());
+
+        let client = Client::builder()
+            .no_env()
+            .with_url("http://axiom.invalid")
+            .with_token("xaat-test")
+            .with_transport(transport.clone())
+            .build()?;
+
+        assert!(client.datasets().list().await?.is_empty());
+
+        let requests = transport.requests.lock().expect("lock is not poisoned");
+        assert_eq!(
+            *requests,
+            vec![(
+                http::Method::GET,
+                "/v1/datasets".to_string(),
+                Some("Bearer xaat-test".to_string())
+            )]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_with_http_client() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/datasets")
+                .header("authorization", "Bearer xaat-test")
+                .header("x-custom", "yes")
+                .header_exists("user-agent");
+            then.status(200).json_body(json!([]));
+        });
+
+        let mut default_headers = http::HeaderMap::new();
+        default_headers.insert("x-custom", http::HeaderValue::from_static("yes"));
+        let http_client = reqwest::Client::builder()
+            .default_headers(default_headers)
+            .build()?;
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_http_client(http_client)
+            .build()?;
+
+        client.datasets().list().await?;
+
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timeouts() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(200)
+                .json_body(json!([]))
+                .delay(std::time::Duration::from_millis(500));
+        });
+        let query_mock = server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/_apl");
+            then.status(200)
+                .json_body(query_page(&[]))
+                .delay(std::time::Duration::from_millis(500));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_timeout(std::time::Duration::from_millis(50))
+            .with_retry_policy(RetryPolicy::disabled())
+            .build()?;
+
+        match client.datasets().list().await {
+            Err(Error::Http(e)) => assert!(e.is_timeout()),
+            res => panic!("Expected timeout, got {:?}", res),
+        }
+
+        // The per-query timeout overrides the client's.
+        client
+            .query(
+                "test",
+                QueryOptions {
+                    timeout: Some(std::time::Duration::from_secs(5)),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        query_mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_options() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let list_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/datasets")
+                .header("x-custom", "foo");
+            then.status(200).json_body(json!([]));
+        });
+        let get_mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets/test");
+            then.status(503)
+                .json_body(json!({ "message": "service unavailable" }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(fast_retry_policy(3))
+            .build()?;
+
+        let mut additional_headers = HeaderMap::new();
+        additional_headers.insert("x-custom", "foo".parse()?);
+        client
+            .datasets()
+            .list_opt(RequestOptions {
+                additional_headers,
+                ..Default::default()
+            })
+            .await?;
+        client
+            .datasets()
+            .list_opt(RequestOptions::default().with_header("x-custom".parse()?, "foo".parse()?))
+            .await?;
+        list_mock.assert_hits_async(2).await;
+
+        // The per-call retry policy overrides the client's.
+        let res = client
+            .datasets()
+            .get_opt(
+                "test",
+                RequestOptions::default().with_retry_policy(RetryPolicy::disabled()),
+            )
+            .await;
+        assert!(res.is_err());
+        get_mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancellation() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(200)
+                .json_body(json!([]))
+                .delay(std::time::Duration::from_secs(5));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        // A cancelled token fails without sending the request.
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = RequestOptions {
+            cancellation_token: Some(token),
+            ..Default::default()
+        };
+        match client.datasets().list_opt(options).await {
+            Err(Error::Cancelled) => {}
+            res => panic!("Expected cancellation, got {:?}", res),
+        }
+        mock.assert_hits_async(0).await;
+
+        // Cancelling aborts a pending request.
+        let token = CancellationToken::new();
+        let options = RequestOptions {
+            cancellation_token: Some(token.clone()),
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            token.cancel();
+        });
+        let started = std::time::Instant::now();
+        match client.datasets().list_opt(options).await {
+            Err(Error::Cancelled) => {}
+            res => panic!("Expected cancellation, got {:?}", res),
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_while_reading_body() -> Result<(), Box<dyn std::error::Error>> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Sends the headers right away, but never the body.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut request = [0; 4096];
+            let _ = socket.read(&mut request).await?;
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: 2\r\n\r\n",
+                )
+                .await?;
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            socket.write_all(b"[]").await
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(format!("http://{addr}"))
+            .with_token("xaat-test")
+            .build()?;
+
+        let token = CancellationToken::new();
+        let options = RequestOptions {
+            cancellation_token: Some(token.clone()),
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            token.cancel();
+        });
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(30),
+            client.datasets().list_opt(options),
+        )
+        .await
+        .expect("cancelling stops reading the body");
+        match res {
+            Err(Error::Cancelled) => {}
+            res => panic!("Expected cancellation, got {:?}", res),
+        }
+        Ok(())
+    }
+
+    /// Logs every request and response it sees, and adds its name to the
+    /// `x-middleware` header.
+    #[derive(Debug)]
+    struct LoggingMiddleware {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for LoggingMiddleware {
+        fn handle<'a>(
+            &'a self,
+            mut request: reqwest::Request,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, crate::error::Result<reqwest::Response>> {
+            Box::pin(async move {
+                let attempt = next.attempt();
+                self.log.lock().expect("lock is not poisoned").push(format!(
+                    "{} request {} {} #{attempt}",
+                    self.name,
+                    request.method(),
+                    request.url().path()
+                ));
+                request
+                    .headers_mut()
+                    .append("x-middleware", self.name.parse().expect("valid header"));
+                let res = next.run(request).await?;
+                self.log.lock().expect("lock is not poisoned").push(format!(
+                    "{} response {} #{attempt}",
+                    self.name,
+                    res.status().as_u16()
+                ));
+                Ok(res)
+            })
+        }
+    }
+
+    /// Fails every request without sending it.
+    #[derive(Debug)]
+    struct RejectingMiddleware;
+
+    impl Middleware for RejectingMiddleware {
+        fn handle<'a>(
+            &'a self,
+            _request: reqwest::Request,
+            _next: Next<'a>,
+        ) -> BoxFuture<'a, crate::error::Result<reqwest::Response>> {
+            Box::pin(async { Err(Error::Cancelled) })
+        }
+    }
+
+    /// Records the highest number of requests in flight at the same time.
+    #[derive(Debug, Default)]
+    struct InFlightMiddleware {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Middleware for InFlightMiddleware {
+        fn handle<'a>(
+            &'a self,
+            request: reqwest::Request,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, crate::error::Result<reqwest::Response>> {
+            use std::sync::atomic::Ordering;
+
+            Box::pin(async move {
+                let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                let res = next.run(request).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                res
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/datasets")
+                .header("x-middleware", "first")
+                .header("x-middleware", "second");
+            then.status(503)
+                .json_body(json!({ "message": "service unavailable" }));
+        });
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(fast_retry_policy(2))
+            .with_middleware(LoggingMiddleware {
+                name: "first",
+                log: log.clone(),
+            })
+            .with_middleware(LoggingMiddleware {
+                name: "second",
+                log: log.clone(),
+            })
+            .build()?;
+
+        assert!(client.datasets().list().await.is_err());
+        mock.assert_hits_async(2).await;
+
+        let log = log.lock().expect("lock is not poisoned").clone();
+        assert_eq!(
+            log,
+            vec![
+                "first request GET /v1/datasets #1",
+                "second request GET /v1/datasets #1",
+                "second response 503 #1",
+                "first response 503 #1",
+                "first request GET /v1/datasets #2",
+                "second request GET /v1/datasets #2",
+                "second response 503 #2",
+                "first response 503 #2",
+            ]
+        );
+
+        // Middleware errors are returned as is and not retried.
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(fast_retry_policy(2))
+            .with_middleware(RejectingMiddleware)
+            .build()?;
+        match client.datasets().list().await {
+            Err(Error::Cancelled) => {}
+            res => panic!("Expected middleware error, got {:?}", res),
+        }
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trace_context() -> Result<(), Box<dyn std::error::Error>> {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/datasets")
+                .header("traceparent", traceparent)
+                .header("tracestate", "congo=t61rcWkgMzE");
+            then.status(200).json_body(json!([]));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_trace_context(move || TraceContext::parse(traceparent, Some("congo=t61rcWkgMzE")))
+            .build()?;
+        client.datasets().list().await?;
+
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200).json_body(json!({
+                "ingested": 1,
+                "failed": 1,
+                "failures": [{ "timestamp": "2021-01-01T00:00:00Z", "error": "invalid" }],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/_apl");
+            then.status(200).json_body(query_page(&[]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(503)
+                .json_body(json!({ "message": "service unavailable" }));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_compression(Compression::Identity)
+            .with_retry_policy(fast_retry_policy(2))
+            .build()?;
+
+        let events = vec![json!({"foo": "bar"}), json!({"foo": "baz"})];
+        client.ingest("test", events).await?;
+        client.query("test", None).await?;
+        assert!(client.datasets().list().await.is_err());
+
+        client.datasets().get("test").await.ok();
+
+        let stats = client.stats();
+        assert_eq!(
+            stats
+                .requests
+                .iter()
+                .map(|((endpoint, status), count)| (endpoint.to_string(), *status, *count))
+                .collect::<Vec<_>>(),
+            vec![
+                ("GET /v1/datasets".to_string(), Some(503), 2),
+                ("GET /v1/datasets/{id}".to_string(), Some(404), 1),
+                ("POST /v1/datasets/{id}/ingest".to_string(), Some(200), 1),
+                ("POST /v1/datasets/_apl".to_string(), Some(200), 1),
+            ]
+        );
+        assert_eq!(
+            stats.requests_by_kind().into_iter().collect::<Vec<_>>(),
+            vec![
+                ((RequestKind::Management, Some(404)), 1),
+                ((RequestKind::Management, Some(503)), 2),
+                ((RequestKind::Ingest, Some(200)), 1),
+                ((RequestKind::Query, Some(200)), 1),
+            ]
+        );
+        let list = Endpoint {
+            kind: RequestKind::Management,
+            method: "GET".to_string(),
+            path: "/v1/datasets".to_string(),
+        };
+        assert_eq!(stats.request_latency[&list].count, 2);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.ingest_bytes_uncompressed, 27);
+        assert_eq!(stats.ingest_bytes_compressed, 27);
+        assert!(stats.bytes_sent > 27);
+        assert_eq!(stats.events_ingested, 1);
+        assert_eq!(stats.events_failed, 1);
+        assert_eq!(stats.query_server_time.count, 1);
+        assert_eq!(
+            stats.query_server_time.sum,
+            std::time::Duration::from_millis(1)
+        );
+        assert_eq!(stats.query_wall_time.count, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_helpers() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets/missing");
+            then.status(404)
+                .header("x-axiom-trace-id", "abc")
+                .json_body(json!({ "message": "dataset not found" }));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets/broken");
+            then.status(502).body("<html>Bad Gateway</html>");
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(RetryPolicy::disabled())
+            .build()?;
+
+        let e = client
+            .datasets()
+            .get("missing")
+            .await
+            .expect_err("dataset is missing");
+        assert_eq!(e.status(), Some(404));
+        assert!(e.is_not_found());
+        assert!(!e.is_unauthorized());
+        assert!(!e.is_retryable());
+        assert_eq!(e.trace_id(), Some("abc"));
+        assert_eq!(e.retry_after(), None);
+        match e {
+            Error::Axiom(e) => {
+                assert_eq!(e.message.as_deref(), Some("dataset not found"));
+                assert_eq!(e.body, None);
+            }
+            e => panic!("Expected Axiom error, got {:?}", e),
+        }
+
+        let e = client
+            .datasets()
+            .get("broken")
+            .await
+            .expect_err("gateway is broken");
+        assert_eq!(e.status(), Some(502));
+        assert!(e.is_retryable());
+        match e {
+            Error::Axiom(e) => {
+                assert_eq!(e.message, None);
+                assert_eq!(e.body.as_deref(), Some("<html>Bad Gateway</html>"));
+            }
+            e => panic!("Expected Axiom error, got {:?}", e),
+        }
+
+        assert!(!Error::Cancelled.is_retryable());
+        assert_eq!(Error::Cancelled.status(), None);
+
+        // Errors that will happen again when retrying.
+        server.mock(|when, then| {
+            when.method(GET).path("/invalid-json");
+            then.status(200).body("not json");
+        });
+        let decode_error = reqwest::get(server.url("/invalid-json"))
+            .await?
+            .json::<serde_json::Value>()
+            .await
+            .expect_err("body is not JSON");
+        assert!(decode_error.is_decode());
+        assert!(!Error::Http(decode_error).is_retryable());
+        let builder_error = reqwest::Client::new()
+            .get("not a url")
+            .build()
+            .expect_err("URL is invalid");
+        assert!(!Error::Http(builder_error).is_retryable());
+
+        // Nothing listens on port 1, so connecting fails.
+        let connect_error = reqwest::get("http://127.0.0.1:1")
+            .await
+            .expect_err("connection is refused");
+        assert!(Error::Http(connect_error).is_retryable());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_error_helpers() -> Result<(), Box<dyn std::error::Error>> {
+        let reset = Utc::now() + Duration::seconds(60);
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/v1/datasets");
+            then.status(429)
+                .json_body(json!({ "message": "rate limit exceeded" }))
+                .header(limits::HEADER_RATE_SCOPE, "user")
+                .header(limits::HEADER_RATE_LIMIT, "42")
+                .header(limits::HEADER_RATE_REMAINING, "0")
+                .header(limits::HEADER_RATE_RESET, format!("{}", reset.timestamp()));
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_retry_policy(RetryPolicy::disabled())
+            .build()?;
+
+        let e = client
+            .datasets()
+            .list()
+            .await
+            .expect_err("rate limit is exceeded");
+        assert_eq!(e.status(), Some(429));
+        assert!(e.is_retryable());
+        assert!(e.retry_after().is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_messages() -> Result<(), Box<dyn std::error::Error>> {
+        let mut page = query_page(&[]);
+        page["status"]["messages"] = json!([
+            { "priority": "info", "count": 1, "code": "default_limit_warning" },
+            {
+                "priority": "warn",
+                "count": 2,
+                "code": "missing_column",
+                "text": "missing column foo"
+            }
+        ]);
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/_apl");
+            then.status(200).json_body(page);
+        });
+
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let res = client.query("test", None).await?;
+        let warnings: Vec<_> = res.warnings().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].priority(), QueryMessagePriority::Warn);
+        assert_eq!(warnings[0].code(), QueryMessageCode::MissingColumn);
+        assert_eq!(warnings[0].count(), 2);
+        assert_eq!(warnings[0].text(), Some("missing column foo"));
+        assert_eq!(res.errors().count(), 0);
+        assert_eq!(res.messages_at_least(QueryMessagePriority::Info).count(), 2);
+
+        let strict = |priority| QueryOptions {
+            strict: Some(priority),
+            ..Default::default()
+        };
+        client
+            .query("test", strict(QueryMessagePriority::Error))
+            .await?;
+        match client
+            .query("test", strict(QueryMessagePriority::Warn))
+            .await
+        {
+            Err(Error::QueryMessages { priority, messages }) => {
+                assert_eq!(priority, QueryMessagePriority::Warn);
+                assert_eq!(messages, warnings.into_iter().cloned().collect::<Vec<_>>());
+            }
+            res => panic!("Expected query messages error, got {:?}", res),
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,645 @@
+//! Buffered ingestion of single events in the background.
+//!
+//! You're probably looking for the [`BatchIngester`].
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::{Client, Error};
+//! use serde_json::json;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Error> {
+//!     let client = Client::new()?;
+//!     let ingester = client.batch_ingester(None);
+//!
+//!     // Queue an event, this returns immediately.
+//!     ingester.send("my-dataset", json!({"foo": "bar"}))?;
+//!
+//!     // Ingest everything that is still buffered and stop the background task.
+//!     let status = ingester.shutdown().await?;
+//!     dbg!(status);
+//!
+//!     Ok(())
+//! }
+//! ```
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, Either},
+    pin_mut,
+    stream::{self, Stream},
+    StreamExt,
+};
+use serde::Serialize;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{
+    datasets::{IngestOptions, IngestStatus, IngestStreamOptions},
+    error::{Error, Result},
+    runtime, Client,
+};
+
+/// Events from these targets are never sent by the logging integrations.
+/// Sending data to Axiom emits events from them, which would then be sent to
+/// Axiom again.
+#[cfg(any(feature = "tracing", feature = "log"))]
+const IGNORED_TARGETS: &[&str] = &[
+    "axiom_rs",
+    "reqwest",
+    "hyper",
+    "hyper_util",
+    "hyper_tls",
+    "h2",
+    "rustls",
+    "native_tls",
+    "tokio_native_tls",
+    "tokio",
+    "mio",
+    "want",
+];
+
+/// How long the logging integrations wait for buffered events to be ingested
+/// when flushing.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+thread_local! {
+    /// Set while a [`BatchIngester`] is sending events on this thread.
+    static SENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with [`SENDING`] set, so events emitted by it are ignored by the
+/// logging integrations even if their target isn't in [`IGNORED_TARGETS`].
+fn sending<T>(f: impl FnOnce() -> T) -> T {
+    let previous = SENDING.with(|sending| sending.replace(true));
+    let res = f();
+    SENDING.with(|sending| sending.set(previous));
+    res
+}
+
+/// Returns true if events from the given target must not be sent, or if they
+/// are emitted while sending events.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) fn is_ignored(target: &str) -> bool {
+    SENDING.with(Cell::get) || is_ignored_target(target)
+}
+
+/// Returns true if the given target is one of [`IGNORED_TARGETS`] or a
+/// module of one.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) fn is_ignored_target(target: &str) -> bool {
+    IGNORED_TARGETS.iter().any(|ignored| {
+        target
+            .strip_prefix(ignored)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+/// The options of a [`BatchIngester`].
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Ingest the buffered events of a dataset once it holds this many events.
+    pub max_events: usize,
+    /// Ingest the buffered events of a dataset once their uncompressed NDJSON
+    /// payload reaches this many bytes.
+    pub max_bytes: usize,
+    /// Ingest the buffered events of a dataset once the oldest of them has
+    /// been waiting for this long.
+    pub max_latency: Duration,
+    /// The maximum number of events waiting to be picked up by the background
+    /// task. Once reached, [`BatchIngester::send`] returns
+    /// [`Error::BatchIngesterFull`].
+    pub capacity: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            max_events: 1000,
+            max_bytes: 1024 * 1024,
+            max_latency: Duration::from_secs(1),
+            capacity: 10_000,
+        }
+    }
+}
+
+/// Called by the background task with the result of a flush.
+type Responder = Box<dyn FnOnce(Result<IngestStatus>) + Send>;
+
+enum Message {
+    Event {
+        dataset_name: String,
+        event: Vec<u8>,
+    },
+    Flush(Responder),
+    Shutdown(Responder),
+}
+
+/// Buffers single events per dataset and ingests them in batches from a
+/// background task.
+///
+/// A batch is ingested once it reaches [`BatchOptions::max_events`] or
+/// [`BatchOptions::max_bytes`], or once its oldest event is older than
+/// [`BatchOptions::max_latency`].
+///
+/// The ingester is cheap to clone, all clones share the same background task.
+/// When the last clone is dropped, the remaining events are ingested and the
+/// background task stops. Use [`BatchIngester::shutdown`] to wait for that.
+///
+/// Create it using [`Client::batch_ingester`].
+#[derive(Debug, Clone)]
+pub struct BatchIngester {
+    sender: mpsc::UnboundedSender<Message>,
+    queued: Arc<AtomicUsize>,
+    capacity: usize,
+}
+
+impl BatchIngester {
+    /// Spawns the background task. This needs to be called from within a
+    /// runtime.
+    pub(crate) fn new(client: Client, options: BatchOptions) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let capacity = options.capacity;
+
+        let worker = Worker {
+            client,
+            options,
+            queued: queued.clone(),
+            batches: HashMap::new(),
+            status: IngestStatus::default(),
+            error: None,
+        };
+        runtime::spawn(worker.run(receiver));
+
+        Self {
+            sender,
+            queued,
+            capacity,
+        }
+    }
+
+    /// Queue an event for ingestion into the given dataset. This does not
+    /// wait for the event to be ingested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event cannot be serialized, if too many events
+    /// are queued or if the ingester has been shut down.
+    pub fn send<N, E>(&self, dataset_name: N, event: E) -> Result<()>
+    where
+        N: Into<String>,
+        E: Serialize,
+    {
+        let event = serde_json::to_vec(&event)?;
+
+        if self.queued.fetch_add(1, Ordering::AcqRel) >= self.capacity {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+            return Err(Error::BatchIngesterFull);
+        }
+
+        self.sender
+            .unbounded_send(Message::Event {
+                dataset_name: dataset_name.into(),
+                event,
+            })
+            .map_err(|_e| {
+                self.queued.fetch_sub(1, Ordering::AcqRel);
+                Error::BatchIngesterClosed
+            })
+    }
+
+    /// Ingest all events that have been queued so far.
+    ///
+    /// Returns the combined status of all batches that have been ingested
+    /// since the last flush, including the ones ingested because a limit was
+    /// reached.
+    ///
+    /// # Errors
+    ///
+    /// Returns the most recent error if ingesting any batch since the last
+    /// flush failed, or an error if the ingester has been shut down.
+    pub async fn flush(&self) -> Result<IngestStatus> {
+        self.request(Message::Flush).await
+    }
+
+    /// Ingest all events that have been queued so far and stop the background
+    /// task. All clones of this ingester will fail to send afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`BatchIngester::flush`].
+    pub async fn shutdown(&self) -> Result<IngestStatus> {
+        self.request(Message::Shutdown).await
+    }
+
+    /// Blocks the current thread until all events queued so far have been
+    /// ingested. Returns `None` if that didn't happen within the timeout or if
+    /// waiting would block the runtime the background task runs on.
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    pub(crate) fn flush_blocking(&self, timeout: Duration) -> Option<Result<IngestStatus>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.sender
+            .unbounded_send(Message::Flush(Box::new(move |res| {
+                sender.send(res).ok();
+            })))
+            .ok()?;
+        runtime::block(|| receiver.recv_timeout(timeout).ok()).flatten()
+    }
+
+    async fn request(&self, message: fn(Responder) -> Message) -> Result<IngestStatus> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .unbounded_send(message(Box::new(move |res| {
+                sender.send(res).ok();
+            })))
+            .map_err(|_e| Error::BatchIngesterClosed)?;
+        receiver.await.map_err(|_e| Error::BatchIngesterClosed)?
+    }
+}
+
+/// Events of a single dataset waiting to be ingested.
+pub(crate) struct Batch {
+    events: Vec<Vec<u8>>,
+    bytes: usize,
+    started_at: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            bytes: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn push(&mut self, event: Vec<u8>) {
+        if self.events.is_empty() {
+            self.started_at = Instant::now();
+        }
+        self.bytes += event.len() + 1;
+        self.events.push(event);
+    }
+
+    /// Joins the events into an NDJSON payload.
+    pub(crate) fn payload(&self) -> Vec<u8> {
+        self.events.join(&b"\n"[..])
+    }
+
+    /// Returns the serialized events.
+    pub(crate) fn into_events(self) -> Vec<Vec<u8>> {
+        self.events
+    }
+}
+
+/// Splits a stream of events into batches as configured in the options.
+/// Stops after the first error.
+pub(crate) fn chunks<S, E>(
+    stream: S,
+    options: &IngestStreamOptions,
+) -> impl Stream<Item = Result<Batch>>
+where
+    S: Stream<Item = Result<E>>,
+    E: Serialize,
+{
+    let chunker = Chunker {
+        stream: Box::pin(stream),
+        max_events: options.max_events.max(1),
+        max_bytes: options.max_bytes.max(1),
+        max_wait: options.max_wait,
+        next: None,
+        done: false,
+    };
+    stream::unfold(chunker, |mut chunker| async move {
+        let batch = chunker.next_batch().await?;
+        Some((batch, chunker))
+    })
+}
+
+/// The state of [`chunks`].
+struct Chunker<S> {
+    stream: Pin<Box<S>>,
+    max_events: usize,
+    max_bytes: usize,
+    max_wait: Duration,
+    /// An event that didn't fit into the previous batch.
+    next: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<S, E> Chunker<S>
+where
+    S: Stream<Item = Result<E>>,
+    E: Serialize,
+{
+    async fn next_batch(&mut self) -> Option<Result<Batch>> {
+        let mut batch = Batch::new();
+        if let Some(event) = self.next.take() {
+            batch.push(event);
+        }
+
+        while !self.done && batch.events.len() < self.max_events && batch.bytes < self.max_bytes {
+            let next = if batch.events.is_empty() {
+                self.stream.next().await
+            } else {
+                let deadline = batch.started_at + self.max_wait;
+                let next = self.stream.next();
+                let sleep = runtime::sleep(deadline.saturating_duration_since(Instant::now()));
+                pin_mut!(sleep);
+                match future::select(next, sleep).await {
+                    Either::Left((next, _)) => next,
+                    Either::Right(_) => break,
+                }
+            };
+
+            let event = match next
+                .map(|res| res.and_then(|e| serde_json::to_vec(&e).map_err(Error::Serialize)))
+            {
+                Some(Ok(event)) => event,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            };
+            if !batch.events.is_empty() && batch.bytes + event.len() + 1 > self.max_bytes {
+                self.next = Some(event);
+                break;
+            }
+            batch.push(event);
+        }
+
+        if batch.events.is_empty() {
+            None
+        } else {
+            Some(Ok(batch))
+        }
+    }
+}
+
+/// The background task of a [`BatchIngester`].
+struct Worker {
+    client: Client,
+    options: BatchOptions,
+    queued: Arc<AtomicUsize>,
+    batches: HashMap<String, Batch>,
+    /// Status of all batches ingested since the last flush.
+    status: IngestStatus,
+    /// The most recent error since the last flush.
+    error: Option<Error>,
+}
+
+impl Worker {
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Message>) {
+        loop {
+            let message = match self.next_deadline() {
+                Some(deadline) => {
+                    let next = receiver.next();
+                    let sleep = runtime::sleep(deadline.saturating_duration_since(Instant::now()));
+                    pin_mut!(sleep);
+                    match future::select(next, sleep).await {
+                        Either::Left((message, _)) => message,
+                        Either::Right(_) => {
+                            self.ingest_expired().await;
+                            continue;
+                        }
+                    }
+                }
+                None => receiver.next().await,
+            };
+
+            match message {
+                Some(Message::Event {
+                    dataset_name,
+                    event,
+                }) => self.push(dataset_name, event).await,
+                Some(Message::Flush(respond)) => respond(self.flush().await),
+                Some(Message::Shutdown(respond)) => {
+                    // Pick up everything that was sent before we stopped
+                    // accepting new messages.
+                    receiver.close();
+                    while let Ok(message) = receiver.try_recv() {
+                        if let Message::Event {
+                            dataset_name,
+                            event,
+                        } = message
+                        {
+                            self.push(dataset_name, event).await;
+                        }
+                    }
+                    respond(self.flush().await);
+                    return;
+                }
+                None => {
+                    // All handles are gone, ingest what's left and stop.
+                    if let Err(e) = self.flush().await {
+                        warn!(error = %e, "Failed to ingest remaining events");
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Returns the point in time at which the oldest batch expires.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.batches
+            .values()
+            .map(|batch| batch.started_at + self.options.max_latency)
+            .min()
+    }
+
+    async fn push(&mut self, dataset_name: String, event: Vec<u8>) {
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+
+        let batch = self
+            .batches
+            .entry(dataset_name.clone())
+            .or_insert_with(Batch::new);
+        batch.push(event);
+
+        if batch.events.len() >= self.options.max_events || batch.bytes >= self.options.max_bytes {
+            if let Some(batch) = self.batches.remove(&dataset_name) {
+                self.ingest(dataset_name, batch).await;
+            }
+        }
+    }
+
+    async fn ingest_expired(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .batches
+            .iter()
+            .filter(|(_, batch)| now.duration_since(batch.started_at) >= self.options.max_latency)
+            .map(|(dataset_name, _)| dataset_name.clone())
+            .collect();
+
+        for dataset_name in expired {
+            if let Some(batch) = self.batches.remove(&dataset_name) {
+                self.ingest(dataset_name, batch).await;
+            }
+        }
+    }
+
+    /// Ingests all remaining batches and returns the status or error
+    /// accumulated since the last flush.
+    async fn flush(&mut self) -> Result<IngestStatus> {
+        for (dataset_name, batch) in mem::take(&mut self.batches) {
+            self.ingest(dataset_name, batch).await;
+        }
+
+        let status = mem::take(&mut self.status);
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(status),
+        }
+    }
+
+    async fn ingest(&mut self, dataset_name: String, batch: Batch) {
+        let events = batch.events.len();
+        let payload = batch.payload();
+        let mut ingest = Box::pin(self.client.ingest_ndjson(
+            &dataset_name,
+            payload,
+            IngestOptions::default(),
+        ));
+        match future::poll_fn(|cx| sending(|| ingest.as_mut().poll(cx))).await {
+            Ok(status) => self.status = mem::take(&mut self.status) + status,
+            Err(e) => {
+                warn!(dataset = %dataset_name, events, error = %e, "Failed to ingest batch");
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use httpmock::prelude::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    use super::BatchOptions;
+    use crate::{Client, Error};
+
+    #[cfg(any(feature = "tracing", feature = "log"))]
+    #[test]
+    fn test_is_ignored() {
+        use super::{is_ignored, sending};
+
+        assert!(is_ignored("native_tls"));
+        assert!(is_ignored("native_tls::imp::openssl"));
+        assert!(is_ignored("hyper_tls::client"));
+        assert!(is_ignored("tokio_native_tls"));
+        assert!(!is_ignored("native_tlsx"));
+        assert!(!is_ignored("app"));
+
+        // Everything emitted while sending is ignored.
+        assert!(sending(|| is_ignored("app")));
+        assert!(!is_ignored("app"));
+    }
+
+    fn ingest_mock(server: &MockServer) -> httpmock::Mock<'_> {
+        server.mock(|when, then| {
+            when.method(POST).path("/v1/datasets/test/ingest");
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        })
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_max_events() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = ingest_mock(&server);
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let ingester = client.batch_ingester(BatchOptions {
+            max_events: 2,
+            max_latency: Duration::from_secs(60),
+            ..Default::default()
+        });
+        for i in 0..3 {
+            ingester.send("test", json!({ "i": i }))?;
+        }
+
+        // The first two events are ingested because of the limit, the third
+        // one by the flush.
+        let status = ingester.flush().await?;
+        assert_eq!(status.ingested, 4);
+        mock.assert_hits_async(2).await;
+
+        // Nothing is buffered anymore.
+        let status = ingester.flush().await?;
+        assert_eq!(status.ingested, 0);
+        mock.assert_hits_async(2).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_max_latency() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = ingest_mock(&server);
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let ingester = client.batch_ingester(BatchOptions {
+            max_latency: Duration::from_millis(50),
+            ..Default::default()
+        });
+        ingester.send("test", json!({"foo": "bar"}))?;
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = ingest_mock(&server);
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let ingester = client.batch_ingester(None);
+        ingester.send("test", json!({"foo": "bar"}))?;
+        ingester.clone().shutdown().await?;
+        mock.assert_hits_async(1).await;
+
+        match ingester.send("test", json!({"foo": "bar"})) {
+            Err(Error::BatchIngesterClosed) => {}
+            res => panic!("Expected closed error, got {:?}", res),
+        }
+        Ok(())
+    }
+}
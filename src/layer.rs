@@ -0,0 +1,413 @@
+//! A [`tracing_subscriber::Layer`] that sends events and spans to Axiom.
+//!
+//! You're probably looking for the [`AxiomLayer`]. This module is only
+//! available with the `tracing` feature.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::{layer::AxiomLayer, Client};
+//! use tracing_subscriber::prelude::*;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = Client::new()?;
+//!     let (layer, _guard) = AxiomLayer::new(client.batch_ingester(None), "my-dataset");
+//!     tracing_subscriber::registry().with(layer).init();
+//!
+//!     tracing::info!(answer = 42, "Hello from tracing");
+//!
+//!     // Buffered events are ingested when `_guard` is dropped.
+//!     Ok(())
+//! }
+//! ```
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value as JsonValue};
+use std::{fmt, time::Instant};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+    Layer,
+};
+
+use crate::{
+    ingester::{is_ignored, BatchIngester, FLUSH_TIMEOUT},
+    trace_context::SpanIds,
+};
+
+/// Sends all events and closed spans to a dataset.
+///
+/// Events are sent with their level, target, source location and fields,
+/// together with the fields of all spans they were recorded in. Spans are
+/// sent when they close, including their duration.
+///
+/// Every span is assigned a W3C trace and span id, which are sent as
+/// `trace_id` and `span_id` with the span and the events recorded in it. These
+/// are the ids [`TraceContext::current`](crate::trace_context::TraceContext::current)
+/// propagates, so requests made inside a span can be linked to it.
+///
+/// Events and spans emitted by this crate and the HTTP stack it uses are
+/// skipped, as are events recorded inside one of this crate's spans.
+pub struct AxiomLayer {
+    ingester: BatchIngester,
+    dataset_name: String,
+}
+
+impl AxiomLayer {
+    /// Creates a new layer sending events to the given dataset through the
+    /// given ingester.
+    ///
+    /// Buffered events are ingested when the returned [`FlushGuard`] is
+    /// dropped, so keep it around until the end of your program.
+    pub fn new<N>(ingester: BatchIngester, dataset_name: N) -> (Self, FlushGuard)
+    where
+        N: Into<String>,
+    {
+        let guard = FlushGuard {
+            ingester: ingester.clone(),
+        };
+        let layer = Self {
+            ingester,
+            dataset_name: dataset_name.into(),
+        };
+        (layer, guard)
+    }
+
+    fn send(&self, event: JsonValue) {
+        // There's nowhere to report this to, the event is dropped.
+        self.ingester.send(&self.dataset_name, event).ok();
+    }
+}
+
+impl fmt::Debug for AxiomLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AxiomLayer")
+            .field("dataset_name", &self.dataset_name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Recorded data of a span, stored in its extensions.
+struct SpanData {
+    fields: Map<String, JsonValue>,
+    started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+impl<S> Layer<S> for AxiomLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let sampled = !span
+                .scope()
+                .any(|span| is_ignored(span.metadata().target()));
+            SpanIds::of(&span, sampled);
+
+            let mut fields = Map::new();
+            attrs.record(&mut JsonVisitor(&mut fields));
+            span.extensions_mut().insert(SpanData {
+                fields,
+                started_at: Utc::now(),
+                started: Instant::now(),
+            });
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(&mut JsonVisitor(&mut data.fields));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if is_ignored(metadata.target()) {
+            return;
+        }
+
+        let mut spans = Vec::new();
+        let mut ids = None;
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if is_ignored(span.metadata().target()) {
+                    return;
+                }
+                ids = Some(SpanIds::of(&span, true));
+                spans.push(span_to_json(&span));
+            }
+        }
+
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let message = fields.remove("message");
+
+        self.send(json!({
+            "_time": Utc::now(),
+            "kind": "event",
+            "trace_id": ids.map(|ids| ids.trace_id_hex()),
+            "span_id": ids.map(|ids| ids.span_id_hex()),
+            "level": metadata.level().to_string(),
+            "target": metadata.target(),
+            "module_path": metadata.module_path(),
+            "file": metadata.file(),
+            "line": metadata.line(),
+            "message": message,
+            "fields": fields,
+            "span": spans.last(),
+            "spans": spans,
+        }));
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut parents = Vec::new();
+        for span in span.scope().from_root() {
+            if is_ignored(span.metadata().target()) {
+                return;
+            }
+            parents.push(span_to_json(&span));
+        }
+        // The scope includes the span itself.
+        parents.pop();
+
+        let data = match span.extensions_mut().remove::<SpanData>() {
+            Some(data) => data,
+            None => return,
+        };
+        let metadata = span.metadata();
+        let ids = SpanIds::of(&span, true);
+        let parent_ids = span.parent().map(|parent| SpanIds::of(&parent, true));
+        self.send(json!({
+            "_time": data.started_at,
+            "kind": "span",
+            "id": id.into_u64(),
+            "trace_id": ids.trace_id_hex(),
+            "span_id": ids.span_id_hex(),
+            "parent_span_id": parent_ids.map(|ids| ids.span_id_hex()),
+            "name": metadata.name(),
+            "level": metadata.level().to_string(),
+            "target": metadata.target(),
+            "module_path": metadata.module_path(),
+            "file": metadata.file(),
+            "line": metadata.line(),
+            "duration_ms": data.started.elapsed().as_secs_f64() * 1000.0,
+            "fields": data.fields,
+            "parent": parents.last(),
+            "spans": parents,
+        }));
+    }
+}
+
+fn span_to_json<S>(span: &SpanRef<'_, S>) -> JsonValue
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let fields = span
+        .extensions()
+        .get::<SpanData>()
+        .map(|data| data.fields.clone())
+        .unwrap_or_default();
+    json!({
+        "id": span.id().into_u64(),
+        "span_id": SpanIds::of(span, true).span_id_hex(),
+        "name": span.name(),
+        "target": span.metadata().target(),
+        "fields": fields,
+    })
+}
+
+/// Records fields into a JSON object.
+struct JsonVisitor<'a>(&'a mut Map<String, JsonValue>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0
+            .insert(field.name().to_string(), json!(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+/// Ingests the events buffered by an [`AxiomLayer`] when dropped.
+///
+/// Dropping the guard blocks for up to five seconds. Inside a current-thread
+/// runtime it can't wait for the background task, flush the ingester
+/// yourself before dropping the guard there.
+#[must_use = "buffered events are ingested when the guard is dropped"]
+#[derive(Debug)]
+pub struct FlushGuard {
+    ingester: BatchIngester,
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        self.ingester.flush_blocking(FLUSH_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::GzDecoder;
+    use httpmock::prelude::*;
+    use serde_json::json;
+    use std::io::Read;
+    use tracing::{info, info_span};
+    use tracing_subscriber::prelude::*;
+
+    use super::AxiomLayer;
+    use crate::{datasets::Compression, trace_context::TraceContext, Client};
+
+    fn body_contains_test_event(req: &HttpMockRequest) -> bool {
+        let body = req.body.as_deref().unwrap_or_default();
+        let mut decoded = String::new();
+        GzDecoder::new(body).read_to_string(&mut decoded).ok();
+        decoded.contains(r#""message":"hello from the test""#)
+            && decoded.contains(r#""name":"outer""#)
+            && decoded.contains(r#""request_id":7"#)
+            && !decoded.contains("from the tls stack")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_layer_sends_events() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .matches(body_contains_test_event);
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let (layer, guard) = AxiomLayer::new(client.batch_ingester(None), "test");
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            // Events from this crate are ignored, so pretend to be someone else.
+            let _span = info_span!(target: "app", "outer", request_id = 7).entered();
+            info!(target: "app", "hello from the test");
+            info!(target: "native_tls::imp", "from the tls stack");
+        });
+
+        drop(guard);
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_layer_sends_trace_ids() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_compression(Compression::Identity)
+            .build()?;
+
+        let (layer, guard) = AxiomLayer::new(client.batch_ingester(None), "test");
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let context = tracing::subscriber::with_default(subscriber, || {
+            let _span = info_span!(target: "app", "outer").entered();
+            info!(target: "app", "hello from the test");
+            TraceContext::current()
+        })
+        .expect("context of the span");
+        assert!(context.sampled);
+
+        // The event and the span carry the ids that would be propagated.
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .body_contains(format!(r#""trace_id":"{:032x}""#, context.trace_id))
+                .body_contains(format!(r#""span_id":"{:016x}""#, context.parent_id));
+            then.status(200).json_body(json!({
+                "ingested": 2,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+
+        drop(guard);
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_layer_trace_context_propagation() -> Result<(), Box<dyn std::error::Error>> {
+        use tracing::Instrument;
+
+        let server = MockServer::start();
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .with_trace_context_propagation()
+            .build()?;
+
+        let (layer, _guard) = AxiomLayer::new(client.batch_ingester(None), "test");
+        let _default = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        let span = info_span!(target: "app", "outer");
+        let context = span
+            .in_scope(TraceContext::current)
+            .expect("context of the span");
+        assert!(context.sampled);
+
+        // The request is linked to the span it was made in, not to the spans
+        // of this crate.
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/v1/datasets")
+                .header("traceparent", context.traceparent());
+            then.status(200).json_body(json!([]));
+        });
+        client.datasets().list().instrument(span).await?;
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+}
@@ -40,21 +40,35 @@
     clippy::pedantic,
     clippy::mod_module_files
 )]
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
 pub mod error;
 mod http;
+pub mod ingester;
+#[cfg(feature = "tracing")]
+pub mod layer;
 pub mod limits;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod middleware;
+#[cfg(feature = "opentelemetry")]
+pub mod otel;
+pub mod retry;
+mod runtime;
 mod serde;
+pub mod stats;
+pub mod trace_context;
 
 pub mod annotations;
 pub mod datasets;
 pub mod users;
 
-pub use client::{Client, RequestOptions};
+pub use client::{CancellationToken, Client, RequestOptions};
 pub use error::Error;
 
 // Exported for `RequestOptions`.
-pub use http::HeaderMap;
+pub use http::{HeaderMap, Transport};
 
 #[doc = include_str!("../README.md")]
 #[cfg(doctest)]
@@ -2,9 +2,11 @@
 
 use chrono::{DateTime, TimeZone, Utc};
 use http::header;
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 use thiserror::Error;
 
+use crate::http::RequestKind;
+
 pub(crate) const HEADER_QUERY_LIMIT: &str = "X-QueryLimit-Limit";
 pub(crate) const HEADER_QUERY_REMAINING: &str = "X-QueryLimit-Remaining";
 pub(crate) const HEADER_QUERY_RESET: &str = "X-QueryLimit-Reset";
@@ -37,50 +39,116 @@ pub(crate) enum Limit {
 
 impl Limit {
     pub(crate) fn try_from(response: &reqwest::Response) -> Option<Self> {
+        let headers = response.headers();
         match response.status().as_u16() {
-            429 => {
-                // Rate limit
-                let scope = response
-                    .headers()
-                    .get(HEADER_RATE_SCOPE)
-                    .and_then(|limit| limit.to_str().ok());
-                let limits = Limits::from_headers(
-                    response.headers(),
-                    HEADER_RATE_LIMIT,
-                    HEADER_RATE_REMAINING,
-                    HEADER_RATE_RESET,
-                )
-                .ok();
-
-                scope
-                    .zip(limits)
-                    .map(|(scope, limits)| Limit::Rate(scope.to_string(), limits))
-            }
-            430 => {
-                // Query or ingest limit
-                let query_limit = Limits::from_headers(
-                    response.headers(),
-                    HEADER_QUERY_LIMIT,
-                    HEADER_QUERY_REMAINING,
-                    HEADER_QUERY_RESET,
-                )
-                .map(Limit::Query)
-                .ok();
-                let ingest_limit = Limits::from_headers(
-                    response.headers(),
-                    HEADER_INGEST_LIMIT,
-                    HEADER_INGEST_REMAINING,
-                    HEADER_INGEST_RESET,
-                )
-                .map(Limit::Ingest)
-                .ok();
-
-                // Can't have both
-                query_limit.or(ingest_limit)
-            }
+            429 => Self::rate(headers),
+            // Can't have both
+            430 => Self::query(headers).or_else(|| Self::ingest(headers)),
             _ => None,
         }
     }
+
+    /// Returns all limits present in the given headers, regardless of whether
+    /// they were exceeded.
+    pub(crate) fn all_from(headers: &header::HeaderMap) -> impl Iterator<Item = Self> {
+        vec![
+            Self::rate(headers),
+            Self::query(headers),
+            Self::ingest(headers),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    fn rate(headers: &header::HeaderMap) -> Option<Self> {
+        let scope = headers
+            .get(HEADER_RATE_SCOPE)
+            .and_then(|limit| limit.to_str().ok());
+        let limits = Limits::from_headers(
+            headers,
+            HEADER_RATE_LIMIT,
+            HEADER_RATE_REMAINING,
+            HEADER_RATE_RESET,
+        )
+        .ok();
+
+        scope
+            .zip(limits)
+            .map(|(scope, limits)| Limit::Rate(scope.to_string(), limits))
+    }
+
+    fn query(headers: &header::HeaderMap) -> Option<Self> {
+        Limits::from_headers(
+            headers,
+            HEADER_QUERY_LIMIT,
+            HEADER_QUERY_REMAINING,
+            HEADER_QUERY_RESET,
+        )
+        .map(Limit::Query)
+        .ok()
+    }
+
+    fn ingest(headers: &header::HeaderMap) -> Option<Self> {
+        Limits::from_headers(
+            headers,
+            HEADER_INGEST_LIMIT,
+            HEADER_INGEST_REMAINING,
+            HEADER_INGEST_RESET,
+        )
+        .map(Limit::Ingest)
+        .ok()
+    }
+
+    pub(crate) fn limits(&self) -> &Limits {
+        match self {
+            Limit::Ingest(limits) | Limit::Query(limits) | Limit::Rate(_, limits) => limits,
+        }
+    }
+}
+
+/// The latest limits reported by the server, as returned by
+/// [`Client::limits`](crate::Client::limits).
+///
+/// A limit is `None` until a response reporting it has been received.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedLimits {
+    /// The scope the rate limit applies to, e.g. `user` or `organization`.
+    pub rate_scope: Option<String>,
+    /// The rate limit, which applies to all requests.
+    pub rate: Option<Limits>,
+    /// The query limit, which applies to queries.
+    pub query: Option<Limits>,
+    /// The ingest limit, which applies to ingest requests.
+    pub ingest: Option<Limits>,
+}
+
+impl RecordedLimits {
+    pub(crate) fn record(&mut self, limit: Limit) {
+        match limit {
+            Limit::Rate(scope, limits) => {
+                self.rate_scope = Some(scope);
+                self.rate = Some(limits);
+            }
+            Limit::Query(limits) => self.query = Some(limits),
+            Limit::Ingest(limits) => self.ingest = Some(limits),
+        }
+    }
+
+    /// Returns how long to wait before sending a request of the given kind
+    /// so it doesn't exceed any of the limits, if at all.
+    pub(crate) fn wait_time(&self, kind: RequestKind) -> Option<Duration> {
+        let kind_limits = match kind {
+            RequestKind::Query => self.query.as_ref(),
+            RequestKind::Ingest => self.ingest.as_ref(),
+            RequestKind::Management => None,
+        };
+        self.rate
+            .iter()
+            .chain(kind_limits)
+            .filter(|limits| limits.is_exceeded())
+            .filter_map(|limits| (limits.reset - Utc::now()).to_std().ok())
+            .max()
+    }
 }
 
 /// Rate-limit information.
@@ -0,0 +1,294 @@
+//! A [`log::Log`] implementation that sends records to Axiom.
+//!
+//! You're probably looking for the [`AxiomLogger`]. This module is only
+//! available with the `log` feature.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::{logger::AxiomLogger, Client};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = Client::new()?;
+//!     AxiomLogger::new(client.batch_ingester(None), "my-dataset")
+//!         .with_max_level(log::LevelFilter::Debug)
+//!         .init()?;
+//!
+//!     log::info!(answer = 42; "Hello from log");
+//!
+//!     // Ingest buffered records before exiting.
+//!     log::logger().flush();
+//!     Ok(())
+//! }
+//! ```
+use chrono::Utc;
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    LevelFilter, Log, Metadata, Record, SetLoggerError,
+};
+use serde_json::{json, Map, Value as JsonValue};
+use std::fmt;
+
+use crate::ingester::{is_ignored, BatchIngester, FLUSH_TIMEOUT};
+
+/// Sends log records to a dataset.
+///
+/// Records are sent with their level, target, module path, source location
+/// and key-values. Records emitted by this crate and the HTTP stack it uses
+/// are skipped.
+///
+/// Records are buffered by the [`BatchIngester`], call [`Log::flush`], e.g.
+/// through `log::logger().flush()`, before your program exits to ingest the
+/// remaining ones.
+pub struct AxiomLogger {
+    ingester: BatchIngester,
+    dataset_name: String,
+    max_level: LevelFilter,
+}
+
+impl AxiomLogger {
+    /// Creates a new logger sending records of level `Info` and above to the
+    /// given dataset through the given ingester.
+    pub fn new<N>(ingester: BatchIngester, dataset_name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            ingester,
+            dataset_name: dataset_name.into(),
+            max_level: LevelFilter::Info,
+        }
+    }
+
+    /// Sets the most verbose level of records to send.
+    #[must_use]
+    pub fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Installs the logger as the global logger and sets the global maximum
+    /// level to the one of the logger.
+    ///
+    /// # Errors
+    ///
+    /// If a global logger was already installed.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = self.max_level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for AxiomLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AxiomLogger")
+            .field("dataset_name", &self.dataset_name)
+            .field("max_level", &self.max_level)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Log for AxiomLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.max_level && !is_ignored(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut fields = Map::new();
+        // Visiting only fails if the visitor does, which ours never does.
+        record
+            .key_values()
+            .visit(&mut JsonVisitor(&mut fields))
+            .ok();
+
+        let event = json!({
+            "_time": Utc::now(),
+            "kind": "log",
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "module_path": record.module_path(),
+            "file": record.file(),
+            "line": record.line(),
+            "message": record.args().to_string(),
+            "fields": fields,
+        });
+        // There's nowhere to report this to, the record is dropped.
+        self.ingester.send(&self.dataset_name, event).ok();
+    }
+
+    /// Blocks for up to five seconds until all buffered records have been
+    /// ingested. Inside a current-thread runtime it can't wait for the
+    /// background task and returns immediately, flush the ingester yourself
+    /// there.
+    fn flush(&self) {
+        self.ingester.flush_blocking(FLUSH_TIMEOUT);
+    }
+}
+
+/// Records key-values into a JSON object.
+struct JsonVisitor<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            json!(value)
+        } else if let Some(value) = value.to_i64() {
+            json!(value)
+        } else if let Some(value) = value.to_u64() {
+            json!(value)
+        } else if let Some(value) = value.to_f64() {
+            json!(value)
+        } else {
+            json!(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::GzDecoder;
+    use httpmock::prelude::*;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use serde_json::json;
+    use std::io::Read;
+
+    use super::AxiomLogger;
+    use crate::Client;
+
+    fn body_contains_test_record(req: &HttpMockRequest) -> bool {
+        let body = req.body.as_deref().unwrap_or_default();
+        let mut decoded = String::new();
+        GzDecoder::new(body).read_to_string(&mut decoded).ok();
+        decoded.contains(r#""message":"hello from the test""#)
+            && decoded.contains(r#""level":"WARN""#)
+            && decoded.contains(r#""request_id":7"#)
+            && decoded.contains(r#""user":"alice""#)
+            && !decoded.contains("too verbose")
+            && !decoded.contains("from the http stack")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logger_sends_records() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .matches(body_contains_test_record);
+            then.status(200).json_body(json!({
+                "ingested": 1,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let logger = AxiomLogger::new(client.batch_ingester(None), "test");
+        let key_values = [("request_id", 7)];
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("app")
+                .args(format_args!("hello from the test"))
+                .key_values(&[("user", "alice")])
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("app")
+                .args(format_args!("with numbers"))
+                .key_values(&key_values)
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Debug)
+                .target("app")
+                .args(format_args!("too verbose"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("reqwest::connect")
+                .args(format_args!("from the http stack"))
+                .build(),
+        );
+
+        logger.flush();
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_logger_skips_tls_records() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/test/ingest")
+                .matches(|req| {
+                    let body = req.body.as_deref().unwrap_or_default();
+                    let mut decoded = String::new();
+                    GzDecoder::new(body).read_to_string(&mut decoded).ok();
+                    decoded.contains("from the app") && !decoded.contains("from the tls stack")
+                });
+            then.status(200).json_body(json!({
+                "ingested": 1,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let logger = AxiomLogger::new(client.batch_ingester(None), "test")
+            .with_max_level(LevelFilter::Trace);
+        for target in ["native_tls", "hyper_tls::client", "tokio_native_tls"] {
+            assert!(!logger.enabled(
+                &Metadata::builder()
+                    .level(Level::Trace)
+                    .target(target)
+                    .build()
+            ));
+            logger.log(
+                &Record::builder()
+                    .level(Level::Debug)
+                    .target(target)
+                    .args(format_args!("from the tls stack"))
+                    .build(),
+            );
+        }
+        logger.log(
+            &Record::builder()
+                .level(Level::Trace)
+                .target("app")
+                .args(format_args!("from the app"))
+                .build(),
+        );
+
+        logger.flush();
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Middleware to observe and modify every request the client sends.
+//!
+//! Register middleware with [`Builder::with_middleware`](crate::client::Builder::with_middleware).
+//! Every attempt of a request, including retries, passes through all
+//! middleware in the order they were registered: the first one sees the
+//! request first and the response last.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::{
+//!     middleware::{Middleware, Next},
+//!     Client,
+//! };
+//! use futures::future::BoxFuture;
+//! use std::time::Instant;
+//!
+//! #[derive(Debug)]
+//! struct AuditLog;
+//!
+//! impl Middleware for AuditLog {
+//!     fn handle<'a>(
+//!         &'a self,
+//!         mut request: reqwest::Request,
+//!         next: Next<'a>,
+//!     ) -> BoxFuture<'a, axiom_rs::error::Result<reqwest::Response>> {
+//!         Box::pin(async move {
+//!             let method = request.method().clone();
+//!             let path = request.url().path().to_string();
+//!             let attempt = next.attempt();
+//!             request
+//!                 .headers_mut()
+//!                 .insert("x-request-source", "audit".parse().expect("valid header"));
+//!
+//!             let started = Instant::now();
+//!             let res = next.run(request).await;
+//!             let status = res.as_ref().map(reqwest::Response::status).ok();
+//!             println!(
+//!                 "{method} {path} (attempt {attempt}): {status:?} in {:?}",
+//!                 started.elapsed()
+//!             );
+//!             res
+//!         })
+//!     }
+//! }
+//!
+//! # fn main() -> Result<(), axiom_rs::Error> {
+//! let client = Client::builder().with_middleware(AuditLog).build()?;
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::BoxFuture;
+use std::{fmt, sync::Arc};
+
+use crate::{
+    error::{Error, Result},
+    Transport,
+};
+
+/// A hook around every attempt of a request.
+///
+/// Implementations usually do something with the request, pass it on with
+/// [`Next::run`] and do something with the response. They can also answer
+/// the request themselves by not calling [`Next::run`].
+///
+/// Responses with error status codes are not errors here, they are handled
+/// after all middleware ran. Errors returned by a middleware other than
+/// [`Error::Http`] fail the request without further retries.
+pub trait Middleware: fmt::Debug + Send + Sync + 'static {
+    /// Handles a request and returns its response.
+    fn handle<'a>(
+        &'a self,
+        request: reqwest::Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<reqwest::Response>>;
+}
+
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    fn handle<'a>(
+        &'a self,
+        request: reqwest::Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<reqwest::Response>> {
+        (**self).handle(request, next)
+    }
+}
+
+/// The rest of the middleware chain, ending in the transport that sends the
+/// request.
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    transport: &'a dyn Transport,
+    attempt: u32,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        middleware: &'a [Arc<dyn Middleware>],
+        transport: &'a dyn Transport,
+        attempt: u32,
+    ) -> Self {
+        Self {
+            middleware,
+            transport,
+            attempt,
+        }
+    }
+
+    /// The number of the current attempt of the request, starting at 1 and
+    /// increasing with every retry.
+    #[must_use]
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Passes the request on to the next middleware, or sends it if this is
+    /// the last one.
+    pub fn run(mut self, request: reqwest::Request) -> BoxFuture<'a, Result<reqwest::Response>> {
+        if let Some((current, rest)) = self.middleware.split_first() {
+            self.middleware = rest;
+            current.handle(request, self)
+        } else {
+            let res = self.transport.send(request);
+            Box::pin(async move { res.await.map_err(Error::Http) })
+        }
+    }
+}
+
+impl fmt::Debug for Next<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("middleware", &self.middleware)
+            .field("attempt", &self.attempt)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,348 @@
+//! An OpenTelemetry [`SpanExporter`] that sends spans to Axiom.
+//!
+//! You're probably looking for the [`AxiomSpanExporter`]. This module is only
+//! available with the `opentelemetry` feature.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::{otel::AxiomSpanExporter, Client};
+//! use opentelemetry::trace::{Tracer, TracerProvider};
+//! use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = Client::new()?;
+//!     let provider = SdkTracerProvider::builder()
+//!         .with_batch_exporter(AxiomSpanExporter::new(client, "my-traces"))
+//!         .with_resource(Resource::builder().with_service_name("my-service").build())
+//!         .build();
+//!
+//!     provider.tracer("my-app").in_span("work", |_cx| {
+//!         // ...
+//!     });
+//!
+//!     // Exports the remaining spans.
+//!     provider.shutdown()?;
+//!     Ok(())
+//! }
+//! ```
+use chrono::{DateTime, Utc};
+use futures::channel::oneshot;
+use opentelemetry::{
+    trace::{SpanId, SpanKind, Status},
+    Array, KeyValue, StringValue, Value,
+};
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    trace::{SpanData, SpanExporter},
+    Resource,
+};
+use serde_json::{json, Map, Value as JsonValue};
+use std::{convert::TryFrom, fmt, future::Future};
+
+use crate::{runtime, Client};
+
+/// Exports spans to a dataset in the shape of Axiom's OpenTelemetry traces.
+///
+/// Every span becomes one event with its `trace_id`, `span_id`,
+/// `parent_span_id`, `name`, `kind`, `duration` in nanoseconds, `status`,
+/// `attributes`, `events` and `links`, together with the `resource` and the
+/// `service` and instrumentation `scope` it came from. The `_time` of the
+/// event is the start of the span.
+///
+/// Spans are ingested with [`Client::ingest`] on the runtime the exporter was
+/// created on, so the batch span processor can run on a thread of its own.
+/// Shutting down the tracer provider from inside a current-thread runtime
+/// blocks that runtime and times out.
+pub struct AxiomSpanExporter {
+    client: Client,
+    dataset_name: String,
+    resource: Map<String, JsonValue>,
+    runtime: runtime::Handle,
+}
+
+impl AxiomSpanExporter {
+    /// Creates a new exporter sending spans to the given dataset.
+    ///
+    /// This needs to be called from within a runtime.
+    pub fn new<N>(client: Client, dataset_name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            client,
+            dataset_name: dataset_name.into(),
+            resource: Map::new(),
+            runtime: runtime::Handle::current(),
+        }
+    }
+}
+
+impl fmt::Debug for AxiomSpanExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AxiomSpanExporter")
+            .field("dataset_name", &self.dataset_name)
+            .field("resource", &self.resource)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpanExporter for AxiomSpanExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        let events: Vec<JsonValue> = batch
+            .iter()
+            .map(|span| span_to_json(span, &self.resource))
+            .collect();
+        let client = self.client.clone();
+        let dataset_name = self.dataset_name.clone();
+        let (sender, receiver) = oneshot::channel();
+        self.runtime.spawn(async move {
+            sender.send(client.ingest(dataset_name, events).await).ok();
+        });
+
+        async move {
+            let status = receiver
+                .await
+                .map_err(|_| OTelSdkError::InternalFailure("Runtime shut down".to_string()))?
+                .map_err(|err| OTelSdkError::InternalFailure(err.to_string()))?;
+            if status.failed > 0 {
+                return Err(OTelSdkError::InternalFailure(format!(
+                    "Failed to ingest {} spans",
+                    status.failed
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource
+            .iter()
+            .map(|(key, value)| (key.to_string(), value_to_json(value)))
+            .collect();
+    }
+}
+
+fn span_to_json(span: &SpanData, resource: &Map<String, JsonValue>) -> JsonValue {
+    let duration = span
+        .end_time
+        .duration_since(span.start_time)
+        .unwrap_or_default();
+    let parent_span_id = if span.parent_span_id == SpanId::INVALID {
+        None
+    } else {
+        Some(span.parent_span_id.to_string())
+    };
+    let (status_code, status_message) = match &span.status {
+        Status::Unset => (None, None),
+        Status::Ok => (Some("OK"), None),
+        Status::Error { description } => (Some("ERROR"), Some(description.as_ref())),
+    };
+    let kind = match span.span_kind {
+        SpanKind::Client => "client",
+        SpanKind::Server => "server",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+        SpanKind::Internal => "internal",
+    };
+    let events: Vec<JsonValue> = span
+        .events
+        .iter()
+        .map(|event| {
+            json!({
+                "name": event.name,
+                "timestamp": DateTime::<Utc>::from(event.timestamp),
+                "attributes": attributes_to_json(&event.attributes),
+            })
+        })
+        .collect();
+    let links: Vec<JsonValue> = span
+        .links
+        .iter()
+        .map(|link| {
+            json!({
+                "trace_id": link.span_context.trace_id().to_string(),
+                "span_id": link.span_context.span_id().to_string(),
+                "attributes": attributes_to_json(&link.attributes),
+            })
+        })
+        .collect();
+
+    json!({
+        "_time": DateTime::<Utc>::from(span.start_time),
+        "trace_id": span.span_context.trace_id().to_string(),
+        "span_id": span.span_context.span_id().to_string(),
+        "parent_span_id": parent_span_id,
+        "name": span.name,
+        "kind": kind,
+        "duration": u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+        "status": {
+            "code": status_code,
+            "message": status_message,
+        },
+        "attributes": attributes_to_json(&span.attributes),
+        "events": events,
+        "links": links,
+        "resource": resource,
+        "service": {
+            "name": resource.get("service.name"),
+        },
+        "scope": {
+            "name": span.instrumentation_scope.name(),
+            "version": span.instrumentation_scope.version(),
+        },
+    })
+}
+
+fn attributes_to_json(attributes: &[KeyValue]) -> Map<String, JsonValue> {
+    attributes
+        .iter()
+        .map(|kv| (kv.key.to_string(), value_to_json(&kv.value)))
+        .collect()
+}
+
+fn value_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Bool(value) => json!(value),
+        Value::I64(value) => json!(value),
+        Value::F64(value) => json!(value),
+        Value::String(value) => json!(value.as_str()),
+        Value::Array(Array::Bool(values)) => json!(values),
+        Value::Array(Array::I64(values)) => json!(values),
+        Value::Array(Array::F64(values)) => json!(values),
+        Value::Array(Array::String(values)) => {
+            json!(values.iter().map(StringValue::as_str).collect::<Vec<_>>())
+        }
+        value => json!(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate2::read::GzDecoder;
+    use httpmock::prelude::*;
+    use opentelemetry::{
+        trace::{Event, SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState},
+        InstrumentationScope, KeyValue,
+    };
+    use opentelemetry_sdk::{
+        trace::{SpanData, SpanEvents, SpanExporter, SpanLinks},
+        Resource,
+    };
+    use serde_json::{json, Value as JsonValue};
+    use std::{
+        io::Read,
+        time::{Duration, SystemTime},
+    };
+
+    use super::AxiomSpanExporter;
+    use crate::Client;
+
+    fn span() -> SpanData {
+        let start_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        let mut events = SpanEvents::default();
+        events.events.push(Event::new(
+            "retry",
+            start_time + Duration::from_millis(5),
+            vec![KeyValue::new("attempt", 2)],
+            0,
+        ));
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736),
+                SpanId::from(0x00f0_67aa_0ba9_02b7),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::from(0x0000_0000_0000_00ab),
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Server,
+            name: "GET /users".into(),
+            start_time,
+            end_time: start_time + Duration::from_millis(12),
+            attributes: vec![
+                KeyValue::new("http.method", "GET"),
+                KeyValue::new("http.status_code", 500),
+            ],
+            dropped_attributes_count: 0,
+            events,
+            links: SpanLinks::default(),
+            status: Status::error("boom"),
+            instrumentation_scope: InstrumentationScope::builder("my-app")
+                .with_version("1.0.0")
+                .build(),
+        }
+    }
+
+    #[test]
+    fn test_span_to_json() {
+        let mut exporter_resource = serde_json::Map::new();
+        exporter_resource.insert("service.name".to_string(), json!("my-service"));
+        let event = super::span_to_json(&span(), &exporter_resource);
+        assert_eq!(
+            event,
+            json!({
+                "_time": "2024-01-01T00:00:00Z",
+                "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736",
+                "span_id": "00f067aa0ba902b7",
+                "parent_span_id": "00000000000000ab",
+                "name": "GET /users",
+                "kind": "server",
+                "duration": 12_000_000,
+                "status": { "code": "ERROR", "message": "boom" },
+                "attributes": { "http.method": "GET", "http.status_code": 500 },
+                "events": [{
+                    "name": "retry",
+                    "timestamp": "2024-01-01T00:00:00.005Z",
+                    "attributes": { "attempt": 2 },
+                }],
+                "links": [],
+                "resource": { "service.name": "my-service" },
+                "service": { "name": "my-service" },
+                "scope": { "name": "my-app", "version": "1.0.0" },
+            })
+        );
+    }
+
+    fn body_contains_test_span(req: &HttpMockRequest) -> bool {
+        let body = req.body.as_deref().unwrap_or_default();
+        let mut decoded = String::new();
+        GzDecoder::new(body).read_to_string(&mut decoded).ok();
+        let event: JsonValue = match serde_json::from_str(decoded.trim()) {
+            Ok(event) => event,
+            Err(_) => return false,
+        };
+        event["trace_id"] == "4bf92f3577b34da6a3ce929d0e0e4736"
+            && event["service"]["name"] == "my-service"
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export() -> Result<(), Box<dyn std::error::Error>> {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/datasets/traces/ingest")
+                .matches(body_contains_test_span);
+            then.status(200).json_body(json!({
+                "ingested": 1,
+                "failed": 0,
+                "failures": [],
+                "processedBytes": 100,
+                "blocksCreated": 0,
+                "walLength": 0
+            }));
+        });
+        let client = Client::builder()
+            .no_env()
+            .with_url(server.base_url())
+            .with_token("xaat-test")
+            .build()?;
+
+        let mut exporter = AxiomSpanExporter::new(client, "traces");
+        exporter.set_resource(&Resource::builder().with_service_name("my-service").build());
+        exporter.export(vec![span()]).await?;
+        mock.assert_hits_async(1).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Retry policy type definitions.
+
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+use std::time::Duration;
+
+/// Returns true if sending a request again may succeed after a response with
+/// the given status code.
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    RetryPolicy::SERVER_ERROR_STATUS_CODES.contains(&status)
+}
+
+/// Controls if and how failed requests are retried.
+///
+/// Retries use an exponential backoff: the first retry happens after
+/// `initial_interval`, every following one waits `multiplier` times as long
+/// as the previous one, up to `max_interval`.
+///
+/// By default, only requests that failed without a response are retried.
+/// Responses with an error status may come after the server already did the
+/// work, so retrying them can e.g. ingest the same events twice. Opt in to
+/// that with [`RetryPolicy::retry_status_codes`].
+///
+/// # Examples
+/// ```no_run
+/// use axiom_rs::{retry::RetryPolicy, Client};
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), axiom_rs::Error> {
+/// let client = Client::builder()
+///     .with_retry_policy(RetryPolicy {
+///         max_attempts: Some(3),
+///         retry_status_codes: RetryPolicy::SERVER_ERROR_STATUS_CODES.to_vec(),
+///         retry_limits: true,
+///         ..Default::default()
+///     })
+///     .build()?;
+///
+/// // Fail fast instead.
+/// let client = Client::builder()
+///     .with_retry_policy(RetryPolicy::disabled())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The delay before the first retry.
+    pub initial_interval: Duration,
+    /// The factor the delay grows by after every retry.
+    pub multiplier: f64,
+    /// The maximum delay between two attempts.
+    pub max_interval: Duration,
+    /// Stop retrying once this much time has passed since the first attempt.
+    /// `None` retries forever, unless `max_attempts` is set.
+    pub max_elapsed_time: Option<Duration>,
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: Option<u32>,
+    /// Randomizes every delay by up to this factor, e.g. `0.5` makes a delay
+    /// of one second anything between 0.5 and 1.5 seconds. `0.0` disables
+    /// jitter.
+    pub jitter: f64,
+    /// Response status codes that are retried. Defaults to none.
+    pub retry_status_codes: Vec<u16>,
+    /// Retry requests that failed without a response, e.g. because the
+    /// connection could not be established or timed out.
+    pub retry_transport_errors: bool,
+    /// Retry requests that exceeded a rate, query or ingest limit (status
+    /// 429 or 430) once the limit resets, as long as that happens within
+    /// `max_elapsed_time`.
+    pub retry_limits: bool,
+}
+
+impl RetryPolicy {
+    /// Internal server errors and temporarily unavailable servers or
+    /// gateways.
+    pub const SERVER_ERROR_STATUS_CODES: &'static [u16] = &[500, 502, 503, 504];
+
+    /// A policy that never retries.
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: Some(1),
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if the given status code should be retried.
+    pub(crate) fn is_retryable_status(&self, status: u16) -> bool {
+        self.retry_status_codes.contains(&status)
+    }
+
+    /// Returns true if the given attempt was the last one allowed.
+    pub(crate) fn is_last_attempt(&self, attempt: u32) -> bool {
+        self.max_attempts
+            .map_or(false, |max_attempts| attempt >= max_attempts)
+    }
+
+    pub(crate) fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_multiplier(self.multiplier)
+            .with_max_interval(self.max_interval)
+            .with_max_elapsed_time(self.max_elapsed_time)
+            .with_randomization_factor(self.jitter)
+            .build()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(500),
+            multiplier: 2.0,
+            max_interval: Duration::from_secs(60),
+            max_elapsed_time: Some(Duration::from_secs(30)),
+            max_attempts: None,
+            jitter: 0.5,
+            retry_status_codes: Vec::new(),
+            retry_transport_errors: true,
+            retry_limits: false,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Thin wrappers around the async runtime selected via features.
+use std::future::Future;
+
+#[cfg(feature = "async-std")]
+pub(crate) use async_std::task::sleep;
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::time::sleep;
+
+/// Spawns a future onto the runtime and detaches it.
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(feature = "tokio")]
+    drop(tokio::task::spawn(future));
+    #[cfg(feature = "async-std")]
+    drop(async_std::task::spawn(future));
+}
+
+/// A handle to the runtime, to spawn futures from threads that don't run
+/// on it.
+#[cfg(feature = "opentelemetry")]
+#[derive(Debug, Clone)]
+pub(crate) struct Handle {
+    #[cfg(feature = "tokio")]
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "opentelemetry")]
+impl Handle {
+    /// Returns a handle to the current runtime. With tokio, this needs to be
+    /// called from within a runtime.
+    pub(crate) fn current() -> Self {
+        Self {
+            #[cfg(feature = "tokio")]
+            handle: tokio::runtime::Handle::current(),
+        }
+    }
+
+    /// Spawns a future onto the runtime and detaches it.
+    #[cfg_attr(not(feature = "tokio"), allow(clippy::unused_self))]
+    pub(crate) fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        #[cfg(feature = "tokio")]
+        drop(self.handle.spawn(future));
+        #[cfg(feature = "async-std")]
+        drop(async_std::task::spawn(future));
+    }
+}
+
+/// Runs a closure that blocks the current thread. Returns `None` without
+/// running it if blocking would stall the runtime the caller is running on.
+#[cfg(any(feature = "tracing", feature = "log"))]
+#[cfg_attr(not(feature = "tokio"), allow(clippy::unnecessary_wraps))]
+pub(crate) fn block<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce() -> R,
+{
+    #[cfg(feature = "tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return match handle.runtime_flavor() {
+            tokio::runtime::RuntimeFlavor::CurrentThread => None,
+            _ => Some(tokio::task::block_in_place(f)),
+        };
+    }
+    Some(f())
+}
+
+/// Drives futures to completion on the current thread, for the blocking
+/// client.
+#[cfg(feature = "blocking")]
+#[derive(Debug, Clone)]
+pub(crate) struct Executor {
+    #[cfg(feature = "tokio")]
+    runtime: std::sync::Arc<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "blocking")]
+impl Executor {
+    #[cfg_attr(not(feature = "tokio"), allow(clippy::unnecessary_wraps))]
+    pub(crate) fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            #[cfg(feature = "tokio")]
+            runtime: std::sync::Arc::new(
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?,
+            ),
+        })
+    }
+
+    #[cfg_attr(not(feature = "tokio"), allow(clippy::unused_self))]
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "tokio")]
+        return self.runtime.block_on(future);
+        #[cfg(feature = "async-std")]
+        return async_std::task::block_on(future);
+    }
+}
@@ -0,0 +1,214 @@
+//! Client-side statistics about the requests a client made.
+//!
+//! Get a snapshot with [`Client::stats`](crate::Client::stats). All values
+//! are totals since the client was built, compute rates by comparing two
+//! snapshots.
+//!
+//! # Examples
+//! ```no_run
+//! use axiom_rs::Client;
+//!
+//! # async fn run() -> Result<(), axiom_rs::Error> {
+//! let client = Client::new()?;
+//! client.datasets().list().await?;
+//!
+//! let stats = client.stats();
+//! for ((endpoint, status), count) in &stats.requests {
+//!     println!("{endpoint} {status:?}: {count}");
+//! }
+//! println!("{} retries", stats.retries);
+//! # Ok(())
+//! # }
+//! ```
+use std::{collections::BTreeMap, convert::TryFrom, fmt, time::Duration};
+
+pub use crate::http::RequestKind;
+
+/// An API endpoint requests were sent to.
+///
+/// The path is a template in which dataset names, ids and other parameters
+/// are replaced by `{id}` and the query string is removed, e.g.
+/// `/v1/datasets/{id}/trim`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Endpoint {
+    /// The kind of requests sent to the endpoint.
+    pub kind: RequestKind,
+    /// The HTTP method, e.g. `GET`.
+    pub method: String,
+    /// The path template, e.g. `/v1/datasets/{id}`.
+    pub path: String,
+}
+
+impl Endpoint {
+    /// Creates the endpoint of a request to the given path, relative to the
+    /// client's base URL.
+    pub(crate) fn new(kind: RequestKind, method: &http::Method, path: &str) -> Self {
+        let path = path.split('?').next().unwrap_or_default();
+        // Paths alternate between collections and their members, e.g.
+        // `/v2/datasets/{id}/fields/{id}`. Members starting with an
+        // underscore are special ones like `_apl`.
+        let segments: Vec<&str> = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .enumerate()
+            .map(|(i, segment)| {
+                if i >= 2 && i % 2 == 0 && !segment.starts_with('_') {
+                    "{id}"
+                } else {
+                    segment
+                }
+            })
+            .collect();
+        Self {
+            kind,
+            method: method.to_string(),
+            path: format!("/{}", segments.join("/")),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+/// A snapshot of the statistics of a client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of requests sent by their endpoint and response status
+    /// code, including retries. Requests that failed without a response have
+    /// no status code.
+    pub requests: BTreeMap<(Endpoint, Option<u16>), u64>,
+    /// The latency of requests by their endpoint, including retries.
+    pub request_latency: BTreeMap<Endpoint, Histogram>,
+    /// The number of retried requests.
+    pub retries: u64,
+    /// The number of request body bytes sent, including retries.
+    pub bytes_sent: u64,
+    /// The size of events serialized by the client before compression.
+    pub ingest_bytes_uncompressed: u64,
+    /// The size of events serialized by the client after compression.
+    pub ingest_bytes_compressed: u64,
+    /// The number of events the server reported as ingested.
+    pub events_ingested: u64,
+    /// The number of events the server reported as failed to ingest.
+    pub events_failed: u64,
+    /// The time queries took to execute on the server, as reported in
+    /// [`QueryStatus::elapsed_time`](crate::datasets::QueryStatus::elapsed_time).
+    pub query_server_time: Histogram,
+    /// The time queries took from sending the request until the result was
+    /// read, including retries.
+    pub query_wall_time: Histogram,
+}
+
+impl Stats {
+    /// Returns the total number of requests sent, including retries.
+    #[must_use]
+    pub fn requests_total(&self) -> u64 {
+        self.requests.values().sum()
+    }
+
+    /// Returns the number of requests sent by their kind and response status
+    /// code, including retries.
+    #[must_use]
+    pub fn requests_by_kind(&self) -> BTreeMap<(RequestKind, Option<u16>), u64> {
+        let mut requests = BTreeMap::new();
+        for ((endpoint, status), count) in &self.requests {
+            *requests.entry((endpoint.kind, *status)).or_default() += count;
+        }
+        requests
+    }
+
+    pub(crate) fn record_request(
+        &mut self,
+        endpoint: &Endpoint,
+        status: Option<u16>,
+        latency: Duration,
+    ) {
+        *self.requests.entry((endpoint.clone(), status)).or_default() += 1;
+        self.request_latency
+            .entry(endpoint.clone())
+            .or_default()
+            .record(latency);
+    }
+}
+
+/// A distribution of durations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// The number of recorded durations.
+    pub count: u64,
+    /// The sum of all recorded durations.
+    pub sum: Duration,
+    /// The longest recorded duration.
+    pub max: Duration,
+    /// The number of recorded durations per bucket. A duration falls into
+    /// the first bucket whose bound in [`Histogram::BOUNDS`] it doesn't
+    /// exceed, the last bucket holds all longer durations.
+    pub buckets: [u64; 12],
+}
+
+impl Histogram {
+    /// The upper bounds of all but the last bucket.
+    pub const BOUNDS: [Duration; 11] = [
+        Duration::from_millis(5),
+        Duration::from_millis(10),
+        Duration::from_millis(25),
+        Duration::from_millis(50),
+        Duration::from_millis(100),
+        Duration::from_millis(250),
+        Duration::from_millis(500),
+        Duration::from_secs(1),
+        Duration::from_millis(2500),
+        Duration::from_secs(5),
+        Duration::from_secs(10),
+    ];
+
+    /// Returns the mean of all recorded durations, if any.
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let mean = self.sum.as_nanos() / u128::from(self.count);
+        Some(Duration::from_nanos(
+            u64::try_from(mean).unwrap_or(u64::MAX),
+        ))
+    }
+
+    pub(crate) fn record(&mut self, duration: Duration) {
+        let bucket = Self::BOUNDS
+            .iter()
+            .position(|bound| duration <= *bound)
+            .unwrap_or(Self::BOUNDS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += duration;
+        self.max = self.max.max(duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Endpoint, RequestKind};
+
+    #[test]
+    fn test_endpoint_path() {
+        for (path, expected) in [
+            ("/v1/datasets", "/v1/datasets"),
+            ("/v1/datasets/logs", "/v1/datasets/{id}"),
+            (
+                "/v2/datasets/logs/fields/foo",
+                "/v2/datasets/{id}/fields/{id}",
+            ),
+            ("/v1/datasets/_apl?format=tabular", "/v1/datasets/_apl"),
+            ("/v1/ingest/logs", "/v1/ingest/{id}"),
+            ("/v1/user", "/v1/user"),
+            ("", "/"),
+        ] {
+            let endpoint = Endpoint::new(RequestKind::Management, &http::Method::GET, path);
+            assert_eq!(endpoint.path, expected, "{path}");
+        }
+    }
+}
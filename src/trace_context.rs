@@ -0,0 +1,289 @@
+//! W3C trace context propagation for outgoing requests.
+//!
+//! With propagation enabled, every request carries a `traceparent` and, if
+//! available, a `tracestate` header as described in the
+//! [W3C Trace Context](https://www.w3.org/TR/trace-context/) specification.
+//! This links the spans of your application to the work Axiom does on the
+//! server, e.g. executing a query.
+//!
+//! Enable it with
+//! [`Builder::with_trace_context`](crate::client::Builder::with_trace_context)
+//! to supply the context yourself, or, with the `tracing` feature, with
+//! [`Builder::with_trace_context_propagation`](crate::client::Builder::with_trace_context_propagation)
+//! to derive it from the current [`tracing`] span.
+//!
+//! Requests that already have a `traceparent` header, e.g. from
+//! [`RequestOptions::additional_headers`](crate::RequestOptions::additional_headers),
+//! are left untouched.
+use futures::future::BoxFuture;
+use http::header::HeaderValue;
+use std::fmt;
+
+#[cfg(feature = "tracing")]
+use crate::ingester::is_ignored_target;
+use crate::{
+    error::Result,
+    middleware::{Middleware, Next},
+};
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+/// The trace context of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The id of the whole trace. Must not be zero.
+    pub trace_id: u128,
+    /// The id of the span the request is made in. Must not be zero.
+    pub parent_id: u64,
+    /// Whether the caller may have recorded the trace.
+    pub sampled: bool,
+    /// Vendor-specific trace information, sent as the `tracestate` header.
+    pub trace_state: Option<String>,
+}
+
+impl TraceContext {
+    /// Parses a context from the values of the `traceparent` and `tracestate`
+    /// headers, e.g. of an incoming request. Returns `None` if the
+    /// `traceparent` is invalid.
+    #[must_use]
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+        // Later versions may append fields, version 00 must not.
+        if version.len() != 2
+            || version == "ff"
+            || (version == "00" && parts.next().is_some())
+            || trace_id.len() != 32
+            || parent_id.len() != 16
+            || flags.len() != 2
+        {
+            return None;
+        }
+
+        let context = Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            parent_id: u64::from_str_radix(parent_id, 16).ok()?,
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+            trace_state: tracestate
+                .map(str::trim)
+                .filter(|state| !state.is_empty())
+                .map(ToString::to_string),
+        };
+        if context.is_valid() {
+            Some(context)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of the `traceparent` header.
+    #[must_use]
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.parent_id,
+            u8::from(self.sampled)
+        )
+    }
+
+    /// Returns the context of the current [`tracing`] span, or `None` if
+    /// there is none.
+    ///
+    /// `tracing` has no notion of trace ids, so random ids are assigned to
+    /// spans: the trace id to the root span, a span id to each span. An
+    /// [`AxiomLayer`](crate::layer::AxiomLayer) assigns them when a span is
+    /// created and sends them with its events and spans, so the context
+    /// points to a trace in your dataset and is marked as sampled. Without
+    /// one, ids are assigned the first time a span is asked for its context
+    /// and the context isn't marked as sampled. Spans of this crate and the
+    /// HTTP stack it uses are skipped in favor of the span they were entered
+    /// in. This requires the subscriber to be based on a
+    /// [`tracing_subscriber::Registry`].
+    #[cfg(feature = "tracing")]
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        let id = tracing::Span::current().id()?;
+        tracing::dispatcher::get_default(|dispatch| {
+            use tracing_subscriber::registry::LookupSpan;
+
+            let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+            // Skip the spans of this crate and the HTTP stack, the request
+            // belongs to the span it was made in.
+            let span = registry
+                .span(&id)?
+                .scope()
+                .find(|span| !is_ignored_target(span.metadata().target()))?;
+            let ids = SpanIds::of(&span, false);
+            Some(Self {
+                trace_id: ids.trace_id,
+                parent_id: ids.span_id,
+                sampled: ids.sampled,
+                trace_state: None,
+            })
+        })
+    }
+
+    /// Returns true if neither id is zero.
+    fn is_valid(&self) -> bool {
+        self.trace_id != 0 && self.parent_id != 0
+    }
+}
+
+/// The W3C ids of a [`tracing`] span, stored in its extensions.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpanIds {
+    pub(crate) trace_id: u128,
+    pub(crate) span_id: u64,
+    /// Whether the span is sent to Axiom.
+    pub(crate) sampled: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl SpanIds {
+    /// Returns the ids of the given span, assigning random ones if it has
+    /// none yet. A new span inherits the trace id of its parent.
+    pub(crate) fn of<'a, R>(
+        span: &tracing_subscriber::registry::SpanRef<'a, R>,
+        sampled: bool,
+    ) -> Self
+    where
+        R: tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        if let Some(ids) = span.extensions().get::<Self>() {
+            return *ids;
+        }
+        let trace_id = span.parent().map_or_else(
+            || rand::random::<u128>().max(1),
+            |parent| Self::of(&parent, false).trace_id,
+        );
+        let ids = Self {
+            trace_id,
+            span_id: rand::random::<u64>().max(1),
+            sampled,
+        };
+        span.extensions_mut().insert(ids);
+        ids
+    }
+
+    /// Returns the trace id as sent in the `traceparent` header.
+    pub(crate) fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    /// Returns the span id as sent in the `traceparent` header.
+    pub(crate) fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+}
+
+/// Adds the trace context returned by a provider to every request.
+pub(crate) struct Propagation<F> {
+    provider: F,
+}
+
+impl<F> Propagation<F> {
+    pub(crate) fn new(provider: F) -> Self {
+        Self { provider }
+    }
+}
+
+impl<F> fmt::Debug for Propagation<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Propagation").finish_non_exhaustive()
+    }
+}
+
+impl<F> Middleware for Propagation<F>
+where
+    F: Fn() -> Option<TraceContext> + Send + Sync + 'static,
+{
+    fn handle<'a>(
+        &'a self,
+        mut request: reqwest::Request,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<reqwest::Response>> {
+        let context = (self.provider)().filter(TraceContext::is_valid);
+        if let Some(context) = context {
+            let headers = request.headers_mut();
+            if !headers.contains_key(TRACEPARENT) {
+                if let Ok(traceparent) = HeaderValue::from_str(&context.traceparent()) {
+                    headers.insert(TRACEPARENT, traceparent);
+                }
+                if let Some(Ok(tracestate)) =
+                    context.trace_state.as_deref().map(HeaderValue::from_str)
+                {
+                    headers.insert(TRACESTATE, tracestate);
+                }
+            }
+        }
+        next.run(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let context = TraceContext::parse(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            Some("congo=t61rcWkgMzE"),
+        )
+        .expect("valid traceparent");
+        assert_eq!(context.trace_id, 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+        assert_eq!(context.parent_id, 0x00f0_67aa_0ba9_02b7);
+        assert!(context.sampled);
+        assert_eq!(context.trace_state.as_deref(), Some("congo=t61rcWkgMzE"));
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473x-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceContext::parse(invalid, None), None, "{invalid}");
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_current() {
+        assert_eq!(TraceContext::current(), None);
+
+        let subscriber = tracing_subscriber::registry();
+        tracing::subscriber::with_default(subscriber, || {
+            // Spans of this crate are skipped, so pretend to be someone else.
+            let root = tracing::info_span!(target: "app", "root");
+            let _root = root.enter();
+            let root_context = TraceContext::current().expect("root context");
+            assert_eq!(TraceContext::current(), Some(root_context.clone()));
+
+            let child = tracing::info_span!(target: "app", "child");
+            let _child = child.enter();
+            let child_context = TraceContext::current().expect("child context");
+            assert_eq!(child_context.trace_id, root_context.trace_id);
+            assert_ne!(child_context.parent_id, root_context.parent_id);
+            // Nothing sends these spans anywhere.
+            assert!(!child_context.sampled);
+
+            let internal = tracing::info_span!("internal");
+            let _internal = internal.enter();
+            assert_eq!(TraceContext::current(), Some(child_context));
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{error::Result, http, users::model::User};
+use crate::{error::Result, http, users::model::User, RequestOptions};
 use tracing::instrument;
 
 /// Provides methods to work with Axiom datasets.
@@ -19,6 +19,20 @@ impl<'client> Client<'client> {
     /// Returns an error if the HTTP request or JSON deserializing fails.
     #[instrument(skip(self))]
     pub async fn current(&self) -> Result<User> {
-        self.http_client.get("/v1/user").await?.json().await
+        self.current_opt(RequestOptions::default()).await
+    }
+
+    /// Like [`Client::current`], but takes [`RequestOptions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP request or JSON deserializing fails.
+    #[instrument(skip(self, request_options))]
+    pub async fn current_opt(&self, request_options: RequestOptions) -> Result<User> {
+        self.http_client
+            .get("/v1/user", request_options)
+            .await?
+            .json()
+            .await
     }
 }
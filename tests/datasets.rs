@@ -184,5 +184,11 @@ async fn test_datasets_impl(ctx: &mut Context) -> Result<(), Box<dyn std::error:
     assert_eq!(1, apl_query_result.tables.len());
     assert_eq!(1000, apl_query_result.tables[0].len());
 
+    // Trim the dataset down to a minimum.
+    ctx.client
+        .datasets()
+        .trim(&ctx.dataset.name, StdDuration::from_secs(1))
+        .await?;
+
     Ok(())
 }